@@ -0,0 +1,13 @@
+/// Whether `flag` was given on the command line, e.g. `--show-mapgen`.
+pub fn arg_flag(flag: &str) -> bool {
+    std::env::args().any(|arg| arg == flag)
+}
+
+/// Returns the value following `flag` on the command line, e.g. `--seed 42`.
+pub fn arg_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
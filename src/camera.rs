@@ -0,0 +1,71 @@
+use super::{draw_map, Hidden, Map, Position, Renderable};
+use rltk::{Point, Rltk};
+use specs::prelude::*;
+
+/// Console columns the map is drawn across.
+pub const VIEW_WIDTH: i32 = 80;
+/// Console rows the map is drawn down; the UI panel takes the rows below.
+pub const VIEW_HEIGHT: i32 = 43;
+
+/// The map tile shown in the top left corner of the screen. The view follows `focus`, but stops
+/// at the edges of maps bigger than the screen and centres maps smaller than it.
+pub fn camera_origin(map: &Map, focus: Point) -> Point {
+    fn axis(focus: i32, view: i32, map: i32) -> i32 {
+        if map <= view {
+            (map - view) / 2
+        } else {
+            (focus - view / 2).clamp(0, map - view)
+        }
+    }
+
+    Point::new(
+        axis(focus.x, VIEW_WIDTH, map.width),
+        axis(focus.y, VIEW_HEIGHT, map.height),
+    )
+}
+
+/// Where map tile `world` is drawn, if it is on screen.
+pub fn world_to_screen(origin: Point, world: Point) -> Option<Point> {
+    let x = world.x - origin.x;
+    let y = world.y - origin.y;
+    if x < 0 || y < 0 || x >= VIEW_WIDTH || y >= VIEW_HEIGHT {
+        return None;
+    }
+    Some(Point::new(x, y))
+}
+
+/// The map tile under console cell `screen`; it may be off the map.
+pub fn screen_to_world(origin: Point, screen: Point) -> Point {
+    Point::new(screen.x + origin.x, screen.y + origin.y)
+}
+
+/// The camera origin for the current map, following the player.
+pub fn player_camera(ecs: &World) -> Point {
+    camera_origin(&ecs.fetch::<Map>(), *ecs.fetch::<Point>())
+}
+
+/// Draws the part of the map around the player and whatever visible entities stand on it.
+pub fn render_camera(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let origin = player_camera(ecs);
+    draw_map(&map, origin, ctx);
+
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let mut data = (&positions, &renderables, !&hidden)
+        .join()
+        .map(|(pos, render, _)| (pos, render))
+        .collect::<Vec<_>>();
+    data.sort_by_key(|&a| std::cmp::Reverse(a.1.render_order));
+
+    for (pos, render) in data.iter() {
+        let idx = map.xy_idx(pos.x, pos.y);
+        if !map.visible_tiles[idx] {
+            continue;
+        }
+        if let Some(screen) = world_to_screen(origin, Point::new(pos.x, pos.y)) {
+            ctx.set(screen.x, screen.y, render.fg, render.bg, render.glyph);
+        }
+    }
+}
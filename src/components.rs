@@ -1,14 +1,20 @@
-use rltk::RGB;
+use super::gamelog::GameLog;
+use super::run_stats::RunStats;
+use super::{GameSeed, MainMenuSelection, Map};
+use rltk::{DiceType, RandomNumberGenerator, RGB};
+use serde::{Deserialize, Serialize};
+use specs::error::NoError;
 use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
 use specs_derive::*;
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Renderable {
     pub glyph: rltk::FontCharType,
     pub fg: RGB,
@@ -16,41 +22,103 @@ pub struct Renderable {
     pub render_order: i32,
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct LeftMover {}
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Player {}
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Viewshed {
     pub visible_tiles: Vec<rltk::Point>,
     pub range: i32,
     pub dirty: bool,
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Monster {}
 
-#[derive(PartialEq, Copy, Clone)]
+/// What a monster is doing between turns; `MonsterAI` moves it from one state to the next.
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum AiState {
+    /// Wakes when it hears the player close by, notices them, or gets hurt.
+    Sleeping,
+    Wandering,
+    /// Heading for where the player was last seen, even after losing sight of them.
+    Chasing {
+        x: i32,
+        y: i32,
+    },
+    /// Lost the player's trail and casting around for them, for this many more turns.
+    Searching {
+        turns: i32,
+    },
+    /// Badly hurt and running from the player.
+    Fleeing,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Ai {
+    pub state: AiState,
+}
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum RunState {
     AwaitingInput,
     PreRun,
     PlayerTurn,
-    MonsterTurn,
+    Ticking,
     ShowInventory,
     ShowDropItem,
-    ShowTargeting { range: i32, item: Entity },
+    ShowRemoveItem,
+    // Games are only saved from the input prompt, so the targeted item never needs remapping
+    #[serde(skip)]
+    ShowTargeting {
+        range: i32,
+        item: Entity,
+    },
+    NextLevel,
+    MainMenu {
+        menu_selection: MainMenuSelection,
+    },
+    SaveGame,
+    GameOver,
+    LevelUp,
+    ShowHighScores,
+    /// Plays back the snapshots taken while the level was generated; see `--show-mapgen`.
+    MapGeneration,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Name {
     pub name: String,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct BlocksTile {}
 
-#[derive(Component, Debug)]
+/// Blocks movement and sight while closed. Anyone can open one by walking into it.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Door {
+    pub open: bool,
+}
+
+impl Door {
+    /// Opens or closes the door standing on `idx`, updating the map straight away rather than
+    /// waiting for the next re-index, and flags every viewshed to be recomputed.
+    pub fn set_open(&mut self, open: bool, renderable: &mut Renderable, map: &mut Map, idx: usize) {
+        self.open = open;
+        renderable.glyph = rltk::to_cp437(if open { '/' } else { '+' });
+        map.blocked[idx] = !open;
+        map.view_blocked_changed = true;
+        if open {
+            map.closed_doors.remove(&idx);
+        } else {
+            map.closed_doors.insert(idx);
+        }
+    }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct CombatStats {
     pub max_hp: i32,
     pub hp: i32,
@@ -58,77 +126,292 @@ pub struct CombatStats {
     pub power: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+/// How quickly an actor earns turns; see `turn_system`.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Energy {
+    pub speed: i32,
+    pub current: i32,
+}
+
+impl Energy {
+    pub fn spend(store: &mut WriteStorage<Energy>, actor: Entity, cost: i32) {
+        if let Some(energy) = store.get_mut(actor) {
+            energy.current -= cost;
+        }
+    }
+}
+
+/// Marks an actor whose energy lets it act this tick; cleared once the monsters have acted.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MyTurn {}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+}
+
+impl Experience {
+    /// Total XP needed to advance past the current level.
+    pub fn next_level_xp(&self) -> i32 {
+        25 * self.level * self.level
+    }
+}
+
+/// XP awarded to whoever lands the killing blow.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct XpValue {
+    pub amount: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct WantsToMelee {
     pub target: Entity,
 }
 
-#[derive(Component, Debug)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Cold,
+    Poison,
+}
+
+impl DamageType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DamageType::Physical => "physical",
+            DamageType::Fire => "fire",
+            DamageType::Cold => "cold",
+            DamageType::Poison => "poison",
+        }
+    }
+}
+
+/// A single hit taken this turn.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Hit {
+    pub amount: i32,
+    pub damage_type: DamageType,
+    /// Whether the player dealt it, so kills can be credited.
+    pub from_player: bool,
+    /// What dealt it, e.g. a monster's or item's name; reported as the cause of death.
+    pub source: String,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct SufferDamage {
-    pub amount: Vec<i32>,
+    pub hits: Vec<Hit>,
 }
 
 impl SufferDamage {
-    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, hit: Hit) {
         if let Some(suffering) = store.get_mut(victim) {
-            suffering.amount.push(amount);
+            suffering.hits.push(hit);
         } else {
-            let dmg = SufferDamage {
-                amount: vec![amount],
-            };
+            let dmg = SufferDamage { hits: vec![hit] };
             store.insert(victim, dmg).expect("Unable to insert damage");
         }
     }
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Item {}
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Consumable {}
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct ProvidesHealing {
     pub heal_amount: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct InBackpack {
     pub owner: Entity,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct WantsToPickupItem {
     pub collected_by: Entity,
     pub item: Entity,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct WantsToDropItem {
     pub item: Entity,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct WantsToUseItem {
     pub item: Entity,
     pub target: Option<rltk::Point>,
 }
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Ranged {
     pub range: i32,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesFood {}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum HungerState {
+    WellFed,
+    Normal,
+    Hungry,
+    Starving,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct HungerClock {
+    pub state: HungerState,
+    /// Turns left until the next state sets in.
+    pub duration: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct InflictsDamage {
     pub damage: i32,
+    pub damage_type: DamageType,
 }
 
-#[derive(Component, Debug)]
+/// Halves damage of these types, on a creature or on the armour it is wearing.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Resistant {
+    pub types: Vec<DamageType>,
+}
+
+/// Doubles damage of these types.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Vulnerable {
+    pub types: Vec<DamageType>,
+}
+
+/// Ignores damage of these types entirely.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Immune {
+    pub types: Vec<DamageType>,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct AreaOfEffect {
     pub radius: i32,
 }
 
-#[derive(Component, Debug)]
-pub struct Confusion {
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum StatusKind {
+    Confusion,
+    Poison,
+    Regeneration,
+    Slow,
+    Haste,
+    Blindness,
+}
+
+impl StatusKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusKind::Confusion => "Confused",
+            StatusKind::Poison => "Poisoned",
+            StatusKind::Regeneration => "Regenerating",
+            StatusKind::Slow => "Slowed",
+            StatusKind::Haste => "Hasted",
+            StatusKind::Blindness => "Blind",
+        }
+    }
+}
+
+/// A status effect, living on an entity of its own and pointing at whoever it affects.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct StatusEffect {
+    pub target: Entity,
+    pub kind: StatusKind,
+    /// The target's turns left before the effect wears off.
+    pub turns: i32,
+    /// Whether the player inflicted it, so a kill by poison is credited to them.
+    pub from_player: bool,
+}
+
+/// Fires its effects on whatever moves onto its tile.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct EntryTrigger {}
+
+/// Keeps an entity off the screen until it is revealed, e.g. a trap until it is sprung.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Hidden {}
+
+/// A trap that is used up the first time it fires.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct SingleActivation {}
+
+/// Marks an entity that changed tile this turn, so traps can check for it.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct EntityMoved {}
+
+/// An item or trap that puts a status effect on whoever it is used on or springs it.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct InflictsStatus {
+    pub kind: StatusKind,
     pub turns: i32,
 }
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Weapon,
+    Armour,
+    Shield,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Equippable {
+    pub slot: EquipmentSlot,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MeleePowerBonus {
+    pub power: i32,
+}
+
+/// The damage dice rolled on a hit, by an equipped weapon or by a creature fighting without one.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MeleeWeapon {
+    pub damage: DiceType,
+    pub damage_type: DamageType,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct DefenseBonus {
+    pub defense: i32,
+}
+
+/// Added to the wearer's speed while equipped; heavy gear has a negative bonus.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct SpeedBonus {
+    pub speed: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToRemoveItem {
+    pub item: Entity,
+}
+
+pub struct SerializeMe;
+
+// Special component that exists to help serialize the game data
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct SerializationHelper {
+    pub map: Map,
+    pub log: GameLog,
+    pub runstate: RunState,
+    pub seed: GameSeed,
+    pub rng: RandomNumberGenerator,
+    pub run_stats: RunStats,
+}
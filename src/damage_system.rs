@@ -1,30 +1,156 @@
 use crate::gamelog::GameLog;
+use crate::high_scores::record_high_score;
+use crate::morgue::write_morgue;
+use crate::run_stats::RunStats;
 
-use super::{CombatStats, Name, Player, SufferDamage};
-use rltk::console;
+use super::{
+    CombatStats, DamageType, Equipped, Experience, Immune, Map, Name, Player, Position, Resistant,
+    RunState, SufferDamage, Vulnerable, XpValue,
+};
 use specs::prelude::*;
 
+/// Extra maximum hit points gained with every level.
+const HP_PER_LEVEL: i32 = 5;
+
+/// Whether `types` on `victim`, or on anything `victim` has equipped, include `damage_type`.
+fn affected_by<T: Component>(
+    store: &ReadStorage<T>,
+    types: fn(&T) -> &Vec<DamageType>,
+    equipped: &ReadStorage<Equipped>,
+    victim: Entity,
+    damage_type: DamageType,
+) -> bool {
+    let on_victim = store
+        .get(victim)
+        .is_some_and(|c| types(c).contains(&damage_type));
+    let on_gear = (store, equipped)
+        .join()
+        .any(|(c, worn)| worn.owner == victim && types(c).contains(&damage_type));
+    on_victim || on_gear
+}
+
 pub struct DamageSystem {}
 
 impl<'a> System<'a> for DamageSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RunStats>,
         WriteStorage<'a, CombatStats>,
         WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, XpValue>,
+        WriteStorage<'a, Experience>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, Resistant>,
+        ReadStorage<'a, Vulnerable>,
+        ReadStorage<'a, Immune>,
+        ReadExpect<'a, Map>,
+        ReadStorage<'a, Position>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage) = data;
+        let (
+            entities,
+            player_entity,
+            mut log,
+            mut run_stats,
+            mut stats,
+            mut damage,
+            xp_values,
+            mut experience,
+            names,
+            equipped,
+            resistant,
+            vulnerable,
+            immune,
+            map,
+            positions,
+        ) = data;
+
+        let mut xp_gained = 0;
+        for (entity, stats, damage) in (&entities, &mut stats, &damage).join() {
+            let was_alive = stats.hp > 0;
+            let victim_name = names.get(entity).map_or("Something", |n| n.name.as_str());
+            // Only hits the player can see are described
+            let seen = positions
+                .get(entity)
+                .is_some_and(|pos| map.visible_tiles[map.xy_idx(pos.x, pos.y)]);
+
+            for hit in damage.hits.iter() {
+                let (amount, damage_type) = (hit.amount, &hit.damage_type);
+                let type_name = damage_type.name();
+                let is_immune = affected_by(&immune, |c| &c.types, &equipped, entity, *damage_type);
+                let resists =
+                    affected_by(&resistant, |c| &c.types, &equipped, entity, *damage_type);
+                let is_vulnerable =
+                    affected_by(&vulnerable, |c| &c.types, &equipped, entity, *damage_type);
+
+                // Resistance and vulnerability to the same type cancel out
+                let taken = if is_immune {
+                    if seen {
+                        log.entries.push(format!(
+                            "{} is immune to {} damage.",
+                            victim_name, type_name
+                        ));
+                    }
+                    0
+                } else if resists && !is_vulnerable {
+                    let taken = amount / 2;
+                    if seen {
+                        log.entries.push(format!(
+                            "{} resists {} {} damage.",
+                            victim_name,
+                            amount - taken,
+                            type_name
+                        ));
+                    }
+                    taken
+                } else if is_vulnerable && !resists {
+                    if seen {
+                        log.entries.push(format!(
+                            "{} takes {} extra {} damage.",
+                            victim_name, amount, type_name
+                        ));
+                    }
+                    amount * 2
+                } else {
+                    amount
+                };
+
+                // The hit that takes the player's last hit point is what killed them
+                if entity == *player_entity && stats.hp > 0 && stats.hp - taken < 1 {
+                    run_stats.cause_of_death = Some(hit.source.clone());
+                }
+                stats.hp -= taken;
+            }
 
-        for (mut stats, damage) in (&mut stats, &damage).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
+            let killed_by_player = damage.hits.iter().any(|hit| hit.from_player);
+            if was_alive && stats.hp < 1 && killed_by_player {
+                if let Some(value) = xp_values.get(entity) {
+                    xp_gained += value.amount;
+                }
+                if entity != *player_entity {
+                    run_stats.kills.push(victim_name.to_string());
+                }
+            }
+        }
+
+        if let Some(player_xp) = experience.get_mut(*player_entity) {
+            player_xp.xp += xp_gained;
         }
 
         damage.clear();
     }
 }
 
-pub fn delete_the_dead(ecs: &mut World) {
+/// Removes slain monsters and ends the run if the player died. A run that ends with
+/// `keep_records` unset, such as one played back from a replay, writes no morgue file or score.
+pub fn delete_the_dead(ecs: &mut World, keep_records: bool) {
     let mut dead: Vec<Entity> = Vec::new();
+    let mut player_died = false;
     {
         let combat_stats = ecs.read_storage::<CombatStats>();
         let players = ecs.read_storage::<Player>();
@@ -43,7 +169,13 @@ pub fn delete_the_dead(ecs: &mut World) {
                         }
                         dead.push(entity)
                     }
-                    Some(_) => console::log("You are dead"),
+                    Some(_) => {
+                        let mut runstate = ecs.write_resource::<RunState>();
+                        if *runstate != RunState::GameOver {
+                            player_died = true;
+                            *runstate = RunState::GameOver;
+                        }
+                    }
                 }
             }
         }
@@ -51,4 +183,50 @@ pub fn delete_the_dead(ecs: &mut World) {
     for victim in dead {
         ecs.delete_entity(victim).expect("Unable to delete");
     }
+
+    if player_died {
+        ecs.write_resource::<GameLog>()
+            .entries
+            .push("You are dead.".to_string());
+    }
+    if player_died && keep_records {
+        if let Some(path) = write_morgue(ecs) {
+            ecs.write_resource::<GameLog>()
+                .entries
+                .push(format!("A record of your run was written to {}.", path));
+        }
+        if let Some(place) = record_high_score(ecs) {
+            ecs.write_resource::<GameLog>()
+                .entries
+                .push(format!("You made the hall of fame at #{}!", place));
+        }
+    }
+
+    check_for_level_up(ecs);
+}
+
+/// Once the monsters have moved, a player with enough XP gains a level: more maximum hit points,
+/// a full heal, and the level-up menu to pick a stat to improve.
+fn check_for_level_up(ecs: &mut World) {
+    if *ecs.fetch::<RunState>() != RunState::AwaitingInput {
+        return;
+    }
+
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut experience = ecs.write_storage::<Experience>();
+    let mut combat_stats = ecs.write_storage::<CombatStats>();
+    if let (Some(xp), Some(stats)) = (
+        experience.get_mut(player_entity),
+        combat_stats.get_mut(player_entity),
+    ) {
+        if stats.hp > 0 && xp.xp >= xp.next_level_xp() {
+            xp.level += 1;
+            stats.max_hp += HP_PER_LEVEL;
+            stats.hp = stats.max_hp;
+            ecs.write_resource::<GameLog>()
+                .entries
+                .push(format!("Welcome to level {}!", xp.level));
+            *ecs.write_resource::<RunState>() = RunState::LevelUp;
+        }
+    }
 }
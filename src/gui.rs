@@ -1,16 +1,24 @@
+use super::camera::{player_camera, screen_to_world, world_to_screen, VIEW_HEIGHT, VIEW_WIDTH};
+use super::high_scores::{load_high_scores, MAX_HIGH_SCORES};
+use super::record_paths::RecordPaths;
 use super::{
-    gamelog::GameLog, CombatStats, InBackpack, Map, Name, Player, Position, State, Viewshed,
-    MAP_HEIGHT, MAP_WIDTH,
+    gamelog::GameLog, run_stats::RunStats, Ai, AiState, CombatStats, Equipped, Experience,
+    GameSeed, Hidden, HungerClock, HungerState, InBackpack, Map, Name, Player, Position, RunState,
+    State, StatusEffect, Viewshed,
 };
-use rltk::{Console, Point, Rltk, VirtualKeyCode, RGB};
+use rltk::{Point, Rltk, VirtualKeyCode, RGB};
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 
+/// The last console row, inside the bottom edge of the UI panel.
+const PANEL_BOTTOM: i32 = VIEW_HEIGHT + 6;
+
 pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     // dialog box
     ctx.draw_box(
         0,
-        MAP_HEIGHT,
-        MAP_WIDTH - 1,
+        VIEW_HEIGHT,
+        VIEW_WIDTH - 1,
         6,
         RGB::named(rltk::WHITE),
         RGB::named(rltk::BLACK),
@@ -19,20 +27,30 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     let stats = ecs.read_storage::<CombatStats>();
     let players = ecs.read_storage::<Player>();
     let log = ecs.fetch::<GameLog>();
+    let map = ecs.fetch::<Map>();
+
+    let depth = format!("Depth: {}", map.depth);
+    ctx.print_color(
+        2,
+        VIEW_HEIGHT,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &depth,
+    );
 
     for (_player, stats) in (&players, &stats).join() {
         let health = format!(" HP: {} / {}", stats.hp, stats.max_hp);
         ctx.print_color(
             12,
-            MAP_HEIGHT,
+            VIEW_HEIGHT,
             RGB::named(rltk::YELLOW),
             RGB::named(rltk::BLACK),
             &health,
         );
         ctx.draw_bar_horizontal(
             28,
-            MAP_HEIGHT,
-            51,
+            VIEW_HEIGHT,
+            30,
             stats.hp,
             stats.max_hp,
             RGB::named(rltk::RED),
@@ -40,9 +58,82 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         );
     }
 
-    let mut y = 44;
+    // Status effects are listed after the HP bar, as far as there is room
+    let player_entity = ecs.fetch::<Entity>();
+    let statuses = ecs.read_storage::<StatusEffect>();
+    let mut x = 60;
+    for status in statuses.join().filter(|s| s.target == *player_entity) {
+        let label = status.kind.name();
+        if x + label.len() as i32 > VIEW_WIDTH - 1 {
+            break;
+        }
+        ctx.print_color(
+            x,
+            VIEW_HEIGHT,
+            RGB::named(rltk::ORANGE),
+            RGB::named(rltk::BLACK),
+            label,
+        );
+        x += label.len() as i32 + 1;
+    }
+
+    let experience = ecs.read_storage::<Experience>();
+    for (_player, xp) in (&players, &experience).join() {
+        let level = format!(
+            "Level: {}  XP: {} / {}",
+            xp.level,
+            xp.xp,
+            xp.next_level_xp()
+        );
+        ctx.print_color(
+            2,
+            PANEL_BOTTOM,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            &level,
+        );
+    }
+
+    let hunger = ecs.read_storage::<HungerClock>();
+    for (_player, clock) in (&players, &hunger).join() {
+        match clock.state {
+            HungerState::WellFed => ctx.print_color(
+                32,
+                PANEL_BOTTOM,
+                RGB::named(rltk::GREEN),
+                RGB::named(rltk::BLACK),
+                "Well Fed",
+            ),
+            HungerState::Normal => {}
+            HungerState::Hungry => ctx.print_color(
+                32,
+                PANEL_BOTTOM,
+                RGB::named(rltk::ORANGE),
+                RGB::named(rltk::BLACK),
+                "Hungry",
+            ),
+            HungerState::Starving => ctx.print_color(
+                32,
+                PANEL_BOTTOM,
+                RGB::named(rltk::RED),
+                RGB::named(rltk::BLACK),
+                "Starving",
+            ),
+        }
+    }
+
+    let seed = format!("Seed: {}", ecs.fetch::<GameSeed>().seed);
+    ctx.print_color(
+        VIEW_WIDTH - seed.len() as i32 - 2,
+        PANEL_BOTTOM,
+        RGB::named(rltk::GREY),
+        RGB::named(rltk::BLACK),
+        &seed,
+    );
+
+    let mut y = VIEW_HEIGHT + 1;
     for s in log.entries.iter().rev() {
-        if y < 49 {
+        if y < PANEL_BOTTOM {
             ctx.print(2, y, s);
             y += 1;
         }
@@ -57,17 +148,28 @@ pub fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
     let names = ecs.read_storage::<Name>();
     let positions = ecs.read_storage::<Position>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let ai = ecs.read_storage::<Ai>();
 
     let mouse_pos = ctx.mouse_pos();
-    if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height {
+    if mouse_pos.0 >= VIEW_WIDTH || mouse_pos.1 >= VIEW_HEIGHT {
         return;
     }
+    let mouse_world = screen_to_world(player_camera(ecs), Point::new(mouse_pos.0, mouse_pos.1));
 
     let mut tooltip: Vec<String> = Vec::new();
-    for (name, position) in (&names, &positions).join() {
+    for (entity, name, position, _hidden) in (&ecs.entities(), &names, &positions, !&hidden).join()
+    {
         let idx = map.xy_idx(position.x, position.y);
-        if position.x == mouse_pos.0 && position.y == mouse_pos.1 && map.visible_tiles[idx] {
-            tooltip.push(name.name.to_string());
+        if position.x == mouse_world.x && position.y == mouse_world.y && map.visible_tiles[idx] {
+            match ai.get(entity).map(|ai| ai.state) {
+                Some(AiState::Sleeping) => tooltip.push(format!("{} (asleep)", name.name)),
+                Some(AiState::Fleeing) => tooltip.push(format!("{} (fleeing)", name.name)),
+                Some(AiState::Searching { .. }) => {
+                    tooltip.push(format!("{} (searching)", name.name))
+                }
+                _ => tooltip.push(name.name.to_string()),
+            }
         }
     }
 
@@ -83,7 +185,7 @@ pub fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     }
     width += 3;
 
-    if mouse_pos.0 > 40 {
+    if mouse_pos.0 > VIEW_WIDTH / 2 {
         let arrow_pos = Point::new(mouse_pos.0 - 2, mouse_pos.1);
         let left_x = mouse_pos.0 - width;
         let mut y = mouse_pos.1;
@@ -102,7 +204,7 @@ pub fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                     y,
                     RGB::named(rltk::WHITE),
                     RGB::named(rltk::GREY),
-                    &" ".to_string(),
+                    " ",
                 );
                 y += 1;
             }
@@ -111,14 +213,14 @@ pub fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                 arrow_pos.y,
                 RGB::named(rltk::WHITE),
                 RGB::named(rltk::GREY),
-                &"->".to_string(),
+                "->",
             );
         }
     } else {
         let arrow_pos = Point::new(mouse_pos.0 + 1, mouse_pos.1);
         let left_x = mouse_pos.0 + 3;
-        let mut y = mouse_pos.1;
-        for s in tooltip.iter() {
+        for (i, s) in tooltip.iter().enumerate() {
+            let y = mouse_pos.1 + i as i32;
             ctx.print_color(
                 left_x + 1,
                 y,
@@ -133,17 +235,16 @@ pub fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                     y,
                     RGB::named(rltk::WHITE),
                     RGB::named(rltk::GREY),
-                    &" ".to_string(),
+                    " ",
                 );
             }
-            y += 1;
         }
         ctx.print_color(
             arrow_pos.x,
             arrow_pos.y,
             RGB::named(rltk::WHITE),
             RGB::named(rltk::GREY),
-            &"<-".to_string(),
+            "<-",
         );
     }
 }
@@ -166,7 +267,7 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
         .filter(|item| item.0.owner == *player_entity);
     let count = inventory.count();
 
-    let mut y = (25 - (count / 2)) as i32;
+    let y = (25 - (count / 2)) as i32;
     ctx.draw_box(
         15,
         y - 2,
@@ -191,11 +292,12 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
     );
 
     let mut equippable: Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names)
+    for (j, (entity, _pack, name)) in (&entities, &backpack, &names)
         .join()
         .filter(|item| item.1.owner == *player_entity)
+        .enumerate()
     {
+        let y = y + j as i32;
         ctx.set(
             17,
             y,
@@ -218,10 +320,8 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, name.name.to_string());
         equippable.push(entity);
-        y += 1;
-        j += 1;
     }
 
     match ctx.key {
@@ -253,7 +353,7 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
         .filter(|item| item.0.owner == *player_entity);
     let count = inventory.count();
 
-    let mut y = (25 - (count / 2)) as i32;
+    let y = (25 - (count / 2)) as i32;
     ctx.draw_box(
         15,
         y - 2,
@@ -278,11 +378,12 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
     );
 
     let mut equippable: Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names)
+    for (j, (entity, _pack, name)) in (&entities, &backpack, &names)
         .join()
         .filter(|item| item.1.owner == *player_entity)
+        .enumerate()
     {
+        let y = y + j as i32;
         ctx.set(
             17,
             y,
@@ -305,10 +406,94 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, name.name.to_string());
+        equippable.push(entity);
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (
+                        ItemMenuResult::Selected,
+                        Some(equippable[selection as usize]),
+                    );
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
+    let entities = gs.ecs.entities();
+
+    let inventory = (&equipped, &names)
+        .join()
+        .filter(|item| item.0.owner == *player_entity);
+    let count = inventory.count();
+
+    let y = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        y - 2,
+        31,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        18,
+        y - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Remove Which Item?",
+    );
+    ctx.print_color(
+        18,
+        y + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "ESCAPE to cancel",
+    );
+
+    let mut equippable: Vec<Entity> = Vec::new();
+    for (j, (entity, _pack, name)) in (&entities, &equipped, &names)
+        .join()
+        .filter(|item| item.1.owner == *player_entity)
+        .enumerate()
+    {
+        let y = y + j as i32;
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as rltk::FontCharType,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, y, name.name.to_string());
         equippable.push(entity);
-        y += 1;
-        j += 1;
     }
 
     match ctx.key {
@@ -337,6 +522,7 @@ pub fn ranged_target(
     let player_entity = gs.ecs.fetch::<Entity>();
     let player_pos = gs.ecs.fetch::<Point>();
     let viewsheds = gs.ecs.read_storage::<Viewshed>();
+    let origin = player_camera(&gs.ecs);
 
     // Highlight available target cells
     let mut available_cells = Vec::new();
@@ -346,7 +532,9 @@ pub fn ranged_target(
         for idx in visible.visible_tiles.iter() {
             let distance = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *idx);
             if distance <= range as f32 {
-                ctx.set_bg(idx.x, idx.y, RGB::named(rltk::BLUE));
+                if let Some(screen) = world_to_screen(origin, *idx) {
+                    ctx.set_bg(screen.x, screen.y, RGB::named(rltk::BLUE));
+                }
                 available_cells.push(idx);
             }
         }
@@ -356,19 +544,17 @@ pub fn ranged_target(
 
     // Draw mouse cursor
     let mouse_pos = ctx.mouse_pos();
+    let mouse_world = screen_to_world(origin, Point::new(mouse_pos.0, mouse_pos.1));
     let mut valid_target = false;
     for idx in available_cells.iter() {
-        if idx.x == mouse_pos.0 && idx.y == mouse_pos.1 {
+        if idx.x == mouse_world.x && idx.y == mouse_world.y {
             valid_target = true;
         }
     }
     if valid_target {
         ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::CYAN));
         if ctx.left_click {
-            return (
-                ItemMenuResult::Selected,
-                Some(Point::new(mouse_pos.0, mouse_pos.1)),
-            );
+            return (ItemMenuResult::Selected, Some(mouse_world));
         }
     } else {
         ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::RED));
@@ -379,3 +565,304 @@ pub fn ranged_target(
 
     (ItemMenuResult::NoResponse, None)
 }
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum MainMenuSelection {
+    NewGame,
+    Continue,
+    HighScores,
+    Quit,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuResult {
+    NoSelection { selected: MainMenuSelection },
+    Selected { selected: MainMenuSelection },
+}
+
+pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
+    let save_exists = super::saveload_system::does_save_exist(&gs.ecs.fetch::<RecordPaths>());
+    let runstate = gs.ecs.fetch::<RunState>();
+
+    ctx.print_color_centered(
+        15,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "rogue-rs",
+    );
+
+    if let RunState::MainMenu {
+        menu_selection: selection,
+    } = *runstate
+    {
+        let mut options = vec![(MainMenuSelection::NewGame, "New Game")];
+        if save_exists {
+            options.push((MainMenuSelection::Continue, "Continue"));
+        }
+        options.push((MainMenuSelection::HighScores, "High Scores"));
+        options.push((MainMenuSelection::Quit, "Quit"));
+
+        for (i, (option, label)) in options.iter().enumerate() {
+            let fg = if selection == *option {
+                RGB::named(rltk::MAGENTA)
+            } else {
+                RGB::named(rltk::WHITE)
+            };
+            ctx.print_color_centered(24 + i as i32, fg, RGB::named(rltk::BLACK), label);
+        }
+
+        let current = options
+            .iter()
+            .position(|(option, _)| *option == selection)
+            .unwrap_or(0);
+
+        match ctx.key {
+            None => {
+                return MainMenuResult::NoSelection {
+                    selected: selection,
+                }
+            }
+            Some(key) => match key {
+                VirtualKeyCode::Escape => {
+                    return MainMenuResult::NoSelection {
+                        selected: MainMenuSelection::Quit,
+                    }
+                }
+                VirtualKeyCode::Up | VirtualKeyCode::K => {
+                    let previous = if current == 0 {
+                        options.len() - 1
+                    } else {
+                        current - 1
+                    };
+                    return MainMenuResult::NoSelection {
+                        selected: options[previous].0,
+                    };
+                }
+                VirtualKeyCode::Down | VirtualKeyCode::J => {
+                    return MainMenuResult::NoSelection {
+                        selected: options[(current + 1) % options.len()].0,
+                    };
+                }
+                VirtualKeyCode::Return => {
+                    return MainMenuResult::Selected {
+                        selected: options[current].0,
+                    }
+                }
+                _ => {
+                    return MainMenuResult::NoSelection {
+                        selected: selection,
+                    }
+                }
+            },
+        }
+    }
+
+    MainMenuResult::NoSelection {
+        selected: MainMenuSelection::NewGame,
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum GameOverResult {
+    NoSelection,
+    QuitToMenu,
+}
+
+pub fn game_over(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
+    let map = ecs.fetch::<Map>();
+    let log = ecs.fetch::<GameLog>();
+
+    ctx.print_color_centered(
+        15,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Your journey has ended!",
+    );
+    ctx.print_color_centered(
+        17,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        format!("You reached depth {} of the dungeon.", map.depth),
+    );
+    let run_stats = ecs.fetch::<RunStats>();
+    ctx.print_color_centered(
+        18,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        format!(
+            "You survived {} turns and killed {} monsters.",
+            run_stats.turns,
+            run_stats.kills.len()
+        ),
+    );
+    ctx.print_color_centered(
+        19,
+        RGB::named(rltk::GREY),
+        RGB::named(rltk::BLACK),
+        format!("Seed: {}", ecs.fetch::<GameSeed>().seed),
+    );
+
+    for (i, s) in log.entries.iter().rev().take(5).enumerate() {
+        ctx.print_color_centered(
+            21 + i as i32,
+            RGB::named(rltk::GREY),
+            RGB::named(rltk::BLACK),
+            s,
+        );
+    }
+
+    ctx.print_color_centered(
+        28,
+        RGB::named(rltk::MAGENTA),
+        RGB::named(rltk::BLACK),
+        "Press any key to see the hall of fame.",
+    );
+
+    match ctx.key {
+        None => GameOverResult::NoSelection,
+        Some(_) => GameOverResult::QuitToMenu,
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum HighScoresResult {
+    NoSelection,
+    Done,
+}
+
+pub fn high_scores(ecs: &World, ctx: &mut Rltk) -> HighScoresResult {
+    let scores = load_high_scores(&ecs.fetch::<RecordPaths>());
+
+    ctx.print_color_centered(
+        8,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Hall of Fame",
+    );
+
+    if scores.is_empty() {
+        ctx.print_color_centered(
+            12,
+            RGB::named(rltk::GREY),
+            RGB::named(rltk::BLACK),
+            "No runs have ended yet.",
+        );
+    } else {
+        ctx.print_color(
+            2,
+            11,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!(
+                "{:>3} {:>6} {:>5} {:>5} {:<10} {:<20} {}",
+                "#", "Score", "Depth", "Kills", "Date", "Killed by", "Seed"
+            ),
+        );
+        for (i, entry) in scores.iter().enumerate() {
+            let cause: String = entry.cause_of_death.chars().take(20).collect();
+            ctx.print_color(
+                2,
+                13 + i as i32,
+                RGB::named(rltk::GREY),
+                RGB::named(rltk::BLACK),
+                format!(
+                    "{:>3} {:>6} {:>5} {:>5} {:<10} {:<20} {}",
+                    i + 1,
+                    entry.score,
+                    entry.depth,
+                    entry.kills,
+                    entry.date,
+                    cause,
+                    entry.seed
+                ),
+            );
+        }
+    }
+
+    ctx.print_color_centered(
+        13 + MAX_HIGH_SCORES as i32 + 2,
+        RGB::named(rltk::MAGENTA),
+        RGB::named(rltk::BLACK),
+        "Press any key to return to the menu.",
+    );
+
+    match ctx.key {
+        None => HighScoresResult::NoSelection,
+        Some(_) => HighScoresResult::Done,
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum LevelUpStat {
+    Power,
+    Defense,
+    Health,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum LevelUpResult {
+    NoSelection,
+    Selected(LevelUpStat),
+}
+
+pub fn level_up_menu(ctx: &mut Rltk) -> LevelUpResult {
+    let options = [
+        (LevelUpStat::Power, "Power +1"),
+        (LevelUpStat::Defense, "Defense +1"),
+        (LevelUpStat::Health, "Max HP +5"),
+    ];
+
+    let y = 25 - options.len() as i32 / 2;
+    ctx.draw_box(
+        15,
+        y - 2,
+        31,
+        options.len() as i32 + 3,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        18,
+        y - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Level Up! Improve which?",
+    );
+
+    for (j, (_stat, label)) in options.iter().enumerate() {
+        let y = y + j as i32;
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as rltk::FontCharType,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+        ctx.print(21, y, label);
+    }
+
+    match ctx.key {
+        None => LevelUpResult::NoSelection,
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < options.len() as i32 {
+                return LevelUpResult::Selected(options[selection as usize].0);
+            }
+            LevelUpResult::NoSelection
+        }
+    }
+}
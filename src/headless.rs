@@ -0,0 +1,226 @@
+use super::damage_system::delete_the_dead;
+use super::gamelog::GameLog;
+use super::gui::LevelUpStat;
+use super::player::{
+    get_item, improve_stat, player_input, try_close_door, try_move_player, try_next_level,
+};
+use super::record_paths::RecordPaths;
+use super::replay::{RecordedInput, ReplayMode};
+use super::{
+    CombatStats, Map, Position, RunState, State, Viewshed, WantsToDropItem, WantsToRemoveItem,
+    WantsToUseItem,
+};
+use rltk::{Point, VirtualKeyCode};
+use specs::prelude::*;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers each harness's records directory, so tests running side by side don't share one.
+static NEXT_RECORDS_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A player action, equivalent to what `player_input` and the item menus produce from keys.
+pub enum Command {
+    Move { delta_x: i32, delta_y: i32 },
+    PickUp,
+    UseItem { item: Entity, target: Option<Point> },
+    DropItem { item: Entity },
+    RemoveItem { item: Entity },
+    CloseDoor,
+    Descend,
+    LevelUp { stat: LevelUpStat },
+}
+
+/// Runs the game without a window: builds the same `World` as `main()`, applies player
+/// commands and advances the systems turn by turn, so tests can inspect the results.
+///
+/// Saves, high scores and morgue files go to a temporary directory of the harness's own, which is
+/// removed when it is dropped.
+pub struct Headless {
+    pub state: State,
+    records_dir: PathBuf,
+}
+
+impl Headless {
+    pub fn new(seed: u64) -> Headless {
+        Headless::start(seed, None)
+    }
+
+    /// Like `new`, but every level is generated by the map builder called `builder`.
+    pub fn with_builder(seed: u64, builder: &str) -> Headless {
+        Headless::start(seed, Some(builder.to_string()))
+    }
+
+    fn start(seed: u64, builder: Option<String>) -> Headless {
+        let records_dir = std::env::temp_dir().join(format!(
+            "rogue-rs-{}-{}",
+            std::process::id(),
+            NEXT_RECORDS_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&records_dir).expect("Unable to create records directory");
+        let mut state = State::new(Some(seed), builder, ReplayMode::Off);
+        state.ecs.insert(RecordPaths::in_dir(&records_dir));
+        state.new_game();
+        state.ecs.insert(RunState::PreRun);
+
+        let mut headless = Headless { state, records_dir };
+        headless.run_until_input();
+        headless
+    }
+
+    /// Applies `command` for the player and runs every following turn until the game
+    /// waits for input again. Returns the state the game ends up in.
+    pub fn command(&mut self, command: Command) -> RunState {
+        let keep_records = !self.state.replay.is_playing();
+        let ecs = &mut self.state.ecs;
+        let player_entity = *ecs.fetch::<Entity>();
+
+        let newrunstate = match command {
+            Command::Move { delta_x, delta_y } => {
+                if try_move_player(delta_x, delta_y, ecs) {
+                    RunState::PlayerTurn
+                } else {
+                    RunState::AwaitingInput
+                }
+            }
+            Command::PickUp => {
+                if get_item(ecs) {
+                    RunState::PlayerTurn
+                } else {
+                    RunState::AwaitingInput
+                }
+            }
+            Command::UseItem { item, target } => {
+                ecs.write_storage::<WantsToUseItem>()
+                    .insert(player_entity, WantsToUseItem { item, target })
+                    .expect("Unable to insert intent");
+                RunState::PlayerTurn
+            }
+            Command::DropItem { item } => {
+                ecs.write_storage::<WantsToDropItem>()
+                    .insert(player_entity, WantsToDropItem { item })
+                    .expect("Unable to insert intent");
+                RunState::PlayerTurn
+            }
+            Command::RemoveItem { item } => {
+                ecs.write_storage::<WantsToRemoveItem>()
+                    .insert(player_entity, WantsToRemoveItem { item })
+                    .expect("Unable to insert intent");
+                RunState::PlayerTurn
+            }
+            Command::LevelUp { stat } => {
+                improve_stat(ecs, stat);
+                RunState::AwaitingInput
+            }
+            Command::CloseDoor => {
+                if try_close_door(ecs) {
+                    RunState::PlayerTurn
+                } else {
+                    RunState::AwaitingInput
+                }
+            }
+            Command::Descend => {
+                if try_next_level(ecs) {
+                    RunState::NextLevel
+                } else {
+                    RunState::AwaitingInput
+                }
+            }
+        };
+
+        *ecs.write_resource::<RunState>() = newrunstate;
+        delete_the_dead(ecs, keep_records);
+        self.run_until_input()
+    }
+
+    /// Presses `key` at the input prompt, read by `player_input` just as a real keypress is, and
+    /// hands it to the replay recorder like `State::tick` does.
+    pub fn press(&mut self, key: VirtualKeyCode) -> RunState {
+        let runstate = *self.state.ecs.fetch::<RunState>();
+        let newrunstate = player_input(&mut self.state, Some(key));
+        self.state.replay.record(
+            runstate,
+            RecordedInput {
+                key: Some(key),
+                mouse_pos: (0, 0),
+                left_click: false,
+            },
+        );
+
+        *self.state.ecs.write_resource::<RunState>() = newrunstate;
+        delete_the_dead(&mut self.state.ecs, !self.state.replay.is_playing());
+        self.run_until_input()
+    }
+
+    /// Advances through the states that need no input, exactly as `State::tick` would.
+    pub fn run_until_input(&mut self) -> RunState {
+        loop {
+            let runstate = *self.state.ecs.fetch::<RunState>();
+            match runstate {
+                RunState::PreRun
+                | RunState::PlayerTurn
+                | RunState::Ticking
+                | RunState::NextLevel => {
+                    let newrunstate = self.state.advance(runstate);
+                    *self.state.ecs.write_resource::<RunState>() = newrunstate;
+                    delete_the_dead(&mut self.state.ecs, !self.state.replay.is_playing());
+                }
+                _ => return runstate,
+            }
+        }
+    }
+
+    pub fn ecs(&self) -> &World {
+        &self.state.ecs
+    }
+
+    pub fn ecs_mut(&mut self) -> &mut World {
+        &mut self.state.ecs
+    }
+
+    pub fn player(&self) -> Entity {
+        *self.state.ecs.fetch::<Entity>()
+    }
+
+    pub fn player_position(&self) -> Position {
+        let positions = self.state.ecs.read_storage::<Position>();
+        positions.get(self.player()).unwrap().clone()
+    }
+
+    pub fn player_stats(&self) -> CombatStats {
+        let stats = self.state.ecs.read_storage::<CombatStats>();
+        stats.get(self.player()).unwrap().clone()
+    }
+
+    pub fn log(&self) -> Vec<String> {
+        self.state.ecs.fetch::<GameLog>().entries.clone()
+    }
+
+    pub fn map(&self) -> Map {
+        (*self.state.ecs.fetch::<Map>()).clone()
+    }
+
+    /// Moves the player straight to `(x, y)`, e.g. to set up a scenario for a test.
+    pub fn teleport_player(&mut self, x: i32, y: i32) {
+        let player_entity = self.player();
+        let ecs = &mut self.state.ecs;
+        if let Some(pos) = ecs.write_storage::<Position>().get_mut(player_entity) {
+            pos.x = x;
+            pos.y = y;
+        }
+        if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(player_entity) {
+            viewshed.dirty = true;
+        }
+        *ecs.write_resource::<Point>() = Point::new(x, y);
+    }
+
+    /// Re-runs the systems outside of a turn so that entities spawned by a test are indexed.
+    pub fn refresh(&mut self) {
+        self.state.run_systems();
+    }
+}
+
+impl Drop for Headless {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.records_dir);
+    }
+}
@@ -0,0 +1,114 @@
+use super::record_paths::RecordPaths;
+use super::run_stats::RunStats;
+use super::seed::GameSeed;
+use super::Map;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+/// How many runs the hall of fame keeps.
+pub const MAX_HIGH_SCORES: usize = 10;
+
+/// A finished run in the hall of fame.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HighScore {
+    pub score: i32,
+    pub depth: i32,
+    pub kills: i32,
+    pub turns: i32,
+    pub seed: u64,
+    pub cause_of_death: String,
+    /// The day the run ended, as `YYYY-MM-DD`.
+    pub date: String,
+}
+
+/// Going deeper counts for the most, then kills, then how long the player lasted.
+pub fn score(depth: i32, kills: i32, turns: i32) -> i32 {
+    depth * 100 + kills * 10 + turns / 10
+}
+
+/// Inserts `entry` into `scores`, which are kept highest first, and drops whatever falls off the
+/// bottom. Returns the entry's place, counting from 1, if it made the table.
+pub fn insert_ranked(scores: &mut Vec<HighScore>, entry: HighScore) -> Option<usize> {
+    // Earlier runs keep their place on a tie
+    let index = scores
+        .iter()
+        .position(|existing| existing.score < entry.score)
+        .unwrap_or(scores.len());
+    if index >= MAX_HIGH_SCORES {
+        return None;
+    }
+    scores.insert(index, entry);
+    scores.truncate(MAX_HIGH_SCORES);
+    Some(index + 1)
+}
+
+/// The hall of fame entry for the run that has just ended.
+pub fn entry_for_run(ecs: &World) -> HighScore {
+    let run_stats = ecs.fetch::<RunStats>();
+    let depth = ecs.fetch::<Map>().depth;
+    let kills = run_stats.kills.len() as i32;
+
+    HighScore {
+        score: score(depth, kills, run_stats.turns),
+        depth,
+        kills,
+        turns: run_stats.turns,
+        seed: ecs.fetch::<GameSeed>().seed,
+        cause_of_death: run_stats
+            .cause_of_death
+            .clone()
+            .unwrap_or_else(|| "unknown causes".to_string()),
+        date: today(),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load_high_scores(_paths: &RecordPaths) -> Vec<HighScore> {
+    Vec::new()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_high_scores(paths: &RecordPaths) -> Vec<HighScore> {
+    std::fs::read_to_string(&paths.high_score_file)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn record_high_score(_ecs: &World) -> Option<usize> {
+    None
+}
+
+/// Adds the run that has just ended to the high score file named in `RecordPaths`, returning its place if it made the
+/// table.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn record_high_score(ecs: &World) -> Option<usize> {
+    let paths = ecs.fetch::<RecordPaths>();
+    let mut scores = load_high_scores(&paths);
+    let place = insert_ranked(&mut scores, entry_for_run(ecs))?;
+    let data = serde_json::to_string_pretty(&scores).ok()?;
+    std::fs::write(&paths.high_score_file, data).ok()?;
+    Some(place)
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`.
+fn today() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0);
+
+    // Converts days since 1970-01-01 to a civil date, after Howard Hinnant's `civil_from_days`
+    let z = seconds / 86_400 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,69 @@
+use super::{gamelog::GameLog, DamageType, Hit, HungerClock, HungerState, RunState, SufferDamage};
+use specs::prelude::*;
+
+/// How many player turns each hunger state lasts before the next one sets in.
+pub const HUNGER_STATE_TURNS: i32 = 200;
+
+pub struct HungerSystem {}
+
+impl<'a> System<'a> for HungerSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, HungerClock>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, RunState>,
+        WriteStorage<'a, SufferDamage>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut hunger_clock, player_entity, runstate, mut inflict_damage, mut log) =
+            data;
+
+        // The clock only runs while the player acts, so waiting on a menu costs nothing
+        if *runstate != RunState::PlayerTurn {
+            return;
+        }
+
+        for (entity, clock) in (&entities, &mut hunger_clock).join() {
+            let is_player = entity == *player_entity;
+
+            // Starving hurts every turn until something is eaten
+            if clock.state == HungerState::Starving {
+                if is_player {
+                    log.entries.push(
+                        "Your hunger pangs are getting painful! You suffer 1 hp damage."
+                            .to_string(),
+                    );
+                }
+                SufferDamage::new_damage(
+                    &mut inflict_damage,
+                    entity,
+                    Hit {
+                        amount: 1,
+                        damage_type: DamageType::Physical,
+                        from_player: false,
+                        source: "starvation".to_string(),
+                    },
+                );
+                continue;
+            }
+
+            clock.duration -= 1;
+            if clock.duration >= 1 {
+                continue;
+            }
+
+            let (next_state, message) = match clock.state {
+                HungerState::WellFed => (HungerState::Normal, "You are no longer well fed."),
+                HungerState::Normal => (HungerState::Hungry, "You are hungry."),
+                _ => (HungerState::Starving, "You are starving!"),
+            };
+            clock.state = next_state;
+            clock.duration = HUNGER_STATE_TURNS;
+            if is_player {
+                log.entries.push(message.to_string());
+            }
+        }
+    }
+}
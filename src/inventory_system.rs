@@ -1,4 +1,10 @@
-use crate::components::{AreaOfEffect, Confusion, Consumable, InflictsDamage, SufferDamage};
+use crate::components::{
+    AreaOfEffect, Consumable, Energy, Equippable, Equipped, Hit, HungerClock, HungerState,
+    InflictsDamage, InflictsStatus, ProvidesFood, StatusEffect, SufferDamage, WantsToRemoveItem,
+};
+use crate::hunger_system::HUNGER_STATE_TURNS;
+use crate::status_effect_system::apply_status;
+use crate::turn_system::{DROP_COST, EQUIP_COST, PICKUP_COST, USE_ITEM_COST};
 
 use super::{
     gamelog::GameLog, CombatStats, InBackpack, Map, Name, Position, ProvidesHealing,
@@ -16,20 +22,31 @@ impl<'a> System<'a> for ItemCollectionSystem {
         WriteStorage<'a, Position>,
         ReadStorage<'a, Name>,
         WriteStorage<'a, InBackpack>,
+        WriteStorage<'a, Energy>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack) =
-            data;
+        let (
+            player_entity,
+            mut gamelog,
+            mut wants_pickup,
+            mut positions,
+            names,
+            mut backpack,
+            mut energy,
+        ) = data;
 
         for pickup in wants_pickup.join() {
+            Energy::spend(&mut energy, pickup.collected_by, PICKUP_COST);
             positions.remove(pickup.item);
-            backpack.insert(
-                pickup.item,
-                InBackpack {
-                    owner: pickup.collected_by,
-                },
-            );
+            backpack
+                .insert(
+                    pickup.item,
+                    InBackpack {
+                        owner: pickup.collected_by,
+                    },
+                )
+                .expect("Unable to insert backpack entry");
 
             if pickup.collected_by == *player_entity {
                 gamelog.entries.push(format!(
@@ -54,6 +71,7 @@ impl<'a> System<'a> for ItemDropSystem {
         ReadStorage<'a, Name>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, InBackpack>,
+        WriteStorage<'a, Energy>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -65,22 +83,26 @@ impl<'a> System<'a> for ItemDropSystem {
             names,
             mut positions,
             mut backpack,
+            mut energy,
         ) = data;
 
         for (entity, to_drop) in (&entities, &wants_drop).join() {
+            Energy::spend(&mut energy, entity, DROP_COST);
             let mut dropper_pos: Position = Position { x: 0, y: 0 };
             {
                 let dropped_pos = positions.get(entity).unwrap();
                 dropper_pos.x = dropped_pos.x;
                 dropper_pos.y = dropped_pos.y;
             }
-            positions.insert(
-                to_drop.item,
-                Position {
-                    x: dropper_pos.x,
-                    y: dropper_pos.y,
-                },
-            );
+            positions
+                .insert(
+                    to_drop.item,
+                    Position {
+                        x: dropper_pos.x,
+                        y: dropper_pos.y,
+                    },
+                )
+                .expect("Unable to insert position");
             backpack.remove(to_drop.item);
 
             if entity == *player_entity {
@@ -112,7 +134,15 @@ impl<'a> System<'a> for ItemUseSystem {
         ReadExpect<'a, Map>,
         WriteStorage<'a, SufferDamage>,
         ReadStorage<'a, AreaOfEffect>,
-        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, InflictsStatus>,
+        WriteStorage<'a, StatusEffect>,
+        Read<'a, LazyUpdate>,
+        ReadStorage<'a, Equippable>,
+        WriteStorage<'a, Equipped>,
+        WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, ProvidesFood>,
+        WriteStorage<'a, HungerClock>,
+        WriteStorage<'a, Energy>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -129,12 +159,27 @@ impl<'a> System<'a> for ItemUseSystem {
             map,
             mut suffer_damage,
             aoe,
-            mut confused,
+            inflicts_status,
+            mut statuses,
+            lazy,
+            equippable,
+            mut equipped,
+            mut backpack,
+            provides_food,
+            mut hunger_clocks,
+            mut energy,
         ) = data;
 
         let mut used_item = false;
 
         for (entity, use_item) in (&entities, &use_item).join() {
+            let cost = if equippable.get(use_item.item).is_some() {
+                EQUIP_COST
+            } else {
+                USE_ITEM_COST
+            };
+            Energy::spend(&mut energy, entity, cost);
+            // Only creatures are affected, not the items, doors or traps sharing their tiles
             let mut targets: Vec<Entity> = Vec::new();
             match use_item.target {
                 None => targets.push(*player_entity),
@@ -144,7 +189,9 @@ impl<'a> System<'a> for ItemUseSystem {
                         None => {
                             let idx = map.xy_idx(target.x, target.y);
                             for mob in map.tile_content[idx].iter() {
-                                targets.push(*mob);
+                                if combat_stats.contains(*mob) {
+                                    targets.push(*mob);
+                                }
                             }
                         }
                         Some(area_effect) => {
@@ -156,7 +203,9 @@ impl<'a> System<'a> for ItemUseSystem {
                             for tile_idx in blast_tiles.iter() {
                                 let idx = map.xy_idx(tile_idx.x, tile_idx.y);
                                 for mob in map.tile_content[idx].iter() {
-                                    targets.push(*mob);
+                                    if combat_stats.contains(*mob) {
+                                        targets.push(*mob);
+                                    }
                                 }
                             }
                         }
@@ -164,6 +213,62 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
+            // Equipping swaps out whatever the wearer already has in that slot
+            if let Some(can_equip) = equippable.get(use_item.item) {
+                let target_slot = can_equip.slot;
+                let target = targets[0];
+
+                let mut to_unequip: Vec<Entity> = Vec::new();
+                for (item_entity, already_equipped, name) in (&entities, &equipped, &names).join() {
+                    if already_equipped.owner == target && already_equipped.slot == target_slot {
+                        to_unequip.push(item_entity);
+                        if target == *player_entity {
+                            gamelog
+                                .entries
+                                .push(format!("You unequip the {}.", name.name));
+                        }
+                    }
+                }
+                for item in to_unequip.iter() {
+                    equipped.remove(*item);
+                    backpack
+                        .insert(*item, InBackpack { owner: target })
+                        .expect("Unable to insert backpack entry");
+                }
+
+                equipped
+                    .insert(
+                        use_item.item,
+                        Equipped {
+                            owner: target,
+                            slot: target_slot,
+                        },
+                    )
+                    .expect("Unable to equip item");
+                backpack.remove(use_item.item);
+                if target == *player_entity {
+                    gamelog.entries.push(format!(
+                        "You equip the {}.",
+                        names.get(use_item.item).unwrap().name
+                    ));
+                }
+            }
+
+            if provides_food.get(use_item.item).is_some() {
+                used_item = true;
+                let target = targets[0];
+                if let Some(clock) = hunger_clocks.get_mut(target) {
+                    clock.state = HungerState::WellFed;
+                    clock.duration = HUNGER_STATE_TURNS;
+                    if entity == *player_entity {
+                        gamelog.entries.push(format!(
+                            "You eat the {}.",
+                            names.get(use_item.item).unwrap().name
+                        ));
+                    }
+                }
+            }
+
             let item_heals = healing.get(use_item.item);
             match item_heals {
                 None => {}
@@ -191,7 +296,16 @@ impl<'a> System<'a> for ItemUseSystem {
                 Some(damage) => {
                     used_item = false;
                     for mob in targets.iter() {
-                        SufferDamage::new_damage(&mut suffer_damage, *mob, damage.damage);
+                        SufferDamage::new_damage(
+                            &mut suffer_damage,
+                            *mob,
+                            Hit {
+                                amount: damage.damage,
+                                damage_type: damage.damage_type,
+                                from_player: entity == *player_entity,
+                                source: names.get(use_item.item).unwrap().name.clone(),
+                            },
+                        );
                         if entity == *player_entity {
                             let mob_name = names.get(*mob).unwrap();
                             let item_name = names.get(use_item.item).unwrap();
@@ -206,32 +320,37 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
-            let mut add_confusion = Vec::new();
-            {
-                let causes_confusion = confused.get(use_item.item);
-                match causes_confusion {
-                    None => {}
-                    Some(confusion) => {
-                        used_item = false;
-                        for mob in targets.iter() {
-                            add_confusion.push((*mob, confusion.turns));
-                            if entity == *player_entity {
-                                let mob_name = names.get(*mob).unwrap();
-                                let item_name = names.get(use_item.item).unwrap();
-                                gamelog.entries.push(format!(
-                                    "You use {} on {}, confusing them.",
-                                    item_name.name, mob_name.name
-                                ));
-                            }
+            if let Some(status) = inflicts_status.get(use_item.item) {
+                used_item = false;
+                for mob in targets.iter() {
+                    apply_status(
+                        &entities,
+                        &lazy,
+                        &mut statuses,
+                        *mob,
+                        status.kind,
+                        status.turns,
+                        entity == *player_entity,
+                    );
+                    if entity == *player_entity {
+                        let item_name = &names.get(use_item.item).unwrap().name;
+                        let effect = status.kind.name().to_lowercase();
+                        if *mob == *player_entity {
+                            gamelog
+                                .entries
+                                .push(format!("You use the {}. You are {}.", item_name, effect));
+                        } else {
+                            gamelog.entries.push(format!(
+                                "You use {} on {}, leaving them {}.",
+                                item_name,
+                                names.get(*mob).unwrap().name,
+                                effect
+                            ));
                         }
                     }
-                }
-            }
 
-            for mob in add_confusion.iter() {
-                confused
-                    .insert(mob.0, Confusion { turns: mob.1 })
-                    .expect("Unable to insert status");
+                    used_item = true;
+                }
             }
 
             if used_item {
@@ -247,3 +366,48 @@ impl<'a> System<'a> for ItemUseSystem {
         use_item.clear();
     }
 }
+
+pub struct ItemRemoveSystem {}
+
+impl<'a> System<'a> for ItemRemoveSystem {
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToRemoveItem>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Equipped>,
+        WriteStorage<'a, InBackpack>,
+        WriteStorage<'a, Energy>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            player_entity,
+            mut gamelog,
+            entities,
+            mut wants_remove,
+            names,
+            mut equipped,
+            mut backpack,
+            mut energy,
+        ) = data;
+
+        for (entity, to_remove) in (&entities, &wants_remove).join() {
+            Energy::spend(&mut energy, entity, EQUIP_COST);
+            equipped.remove(to_remove.item);
+            backpack
+                .insert(to_remove.item, InBackpack { owner: entity })
+                .expect("Unable to insert backpack entry");
+
+            if entity == *player_entity {
+                gamelog.entries.push(format!(
+                    "You unequip the {}.",
+                    names.get(to_remove.item).unwrap().name
+                ));
+            }
+        }
+
+        wants_remove.clear();
+    }
+}
@@ -0,0 +1,575 @@
+use rltk::{GameState, Point, RandomNumberGenerator, Rltk};
+use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
+use specs::{prelude::*, World};
+pub mod components;
+use components::*;
+pub mod player;
+use player::*;
+pub mod map;
+use map::*;
+pub mod rect;
+use rect::*;
+pub mod visibility_system;
+use visibility_system::*;
+pub mod monster_ai_system;
+use monster_ai_system::*;
+pub mod map_indexing_system;
+use map_indexing_system::*;
+pub mod damage_system;
+use damage_system::*;
+pub mod melee_combat_system;
+use melee_combat_system::*;
+pub mod gui;
+use gui::*;
+pub mod inventory_system;
+use inventory_system::*;
+
+pub mod args;
+pub mod camera;
+pub mod gamelog;
+pub mod headless;
+pub mod high_scores;
+pub mod hunger_system;
+pub mod map_builders;
+use map_builders::{LEVEL_HEIGHT, LEVEL_WIDTH};
+pub mod morgue;
+pub mod random_table;
+pub mod raws;
+pub mod record_paths;
+pub mod replay;
+use replay::*;
+pub mod run_stats;
+pub mod saveload_system;
+pub mod seed;
+use seed::*;
+pub mod spawner;
+pub mod status_effect_system;
+pub mod trigger_system;
+pub mod turn_system;
+
+pub struct State {
+    pub ecs: World,
+    /// Seed to start every new game with; a fresh random seed is rolled when this is `None`.
+    pub requested_seed: Option<u64>,
+    /// Map builder to generate every level with; each level picks one at random when this is `None`.
+    pub requested_builder: Option<String>,
+    pub replay: ReplayMode,
+    /// Whether each new level shows how it was generated before play starts.
+    pub show_mapgen: bool,
+    mapgen_history: Vec<Map>,
+    mapgen_index: usize,
+    mapgen_timer: f32,
+    mapgen_next_state: RunState,
+}
+
+/// How long each map generation snapshot stays on screen.
+const MAPGEN_FRAME_MS: f32 = 200.0;
+
+impl State {
+    /// Builds a `State` with every component and resource the game needs registered,
+    /// but no level yet; call `new_game` to populate it.
+    pub fn new(
+        requested_seed: Option<u64>,
+        requested_builder: Option<String>,
+        replay: ReplayMode,
+    ) -> State {
+        let mut gs = State {
+            ecs: World::new(),
+            requested_seed,
+            requested_builder,
+            replay,
+            show_mapgen: false,
+            mapgen_history: Vec::new(),
+            mapgen_index: 0,
+            mapgen_timer: 0.0,
+            mapgen_next_state: RunState::PreRun,
+        };
+
+        gs.ecs.register::<Position>();
+        gs.ecs.register::<Renderable>();
+        gs.ecs.register::<LeftMover>();
+        gs.ecs.register::<Player>();
+        gs.ecs.register::<Viewshed>();
+        gs.ecs.register::<Monster>();
+        gs.ecs.register::<Ai>();
+        gs.ecs.register::<Name>();
+        gs.ecs.register::<BlocksTile>();
+        gs.ecs.register::<Door>();
+        gs.ecs.register::<CombatStats>();
+        gs.ecs.register::<WantsToMelee>();
+        gs.ecs.register::<SufferDamage>();
+        gs.ecs.register::<Item>();
+        gs.ecs.register::<Consumable>();
+        gs.ecs.register::<ProvidesHealing>();
+        gs.ecs.register::<InBackpack>();
+        gs.ecs.register::<WantsToPickupItem>();
+        gs.ecs.register::<WantsToDropItem>();
+        gs.ecs.register::<WantsToUseItem>();
+        gs.ecs.register::<Ranged>();
+        gs.ecs.register::<InflictsDamage>();
+        gs.ecs.register::<AreaOfEffect>();
+        gs.ecs.register::<StatusEffect>();
+        gs.ecs.register::<InflictsStatus>();
+        gs.ecs.register::<EntryTrigger>();
+        gs.ecs.register::<Hidden>();
+        gs.ecs.register::<SingleActivation>();
+        gs.ecs.register::<EntityMoved>();
+        gs.ecs.register::<Equippable>();
+        gs.ecs.register::<Equipped>();
+        gs.ecs.register::<MeleePowerBonus>();
+        gs.ecs.register::<MeleeWeapon>();
+        gs.ecs.register::<Resistant>();
+        gs.ecs.register::<Vulnerable>();
+        gs.ecs.register::<Immune>();
+        gs.ecs.register::<DefenseBonus>();
+        gs.ecs.register::<WantsToRemoveItem>();
+        gs.ecs.register::<Experience>();
+        gs.ecs.register::<XpValue>();
+        gs.ecs.register::<ProvidesFood>();
+        gs.ecs.register::<HungerClock>();
+        gs.ecs.register::<Energy>();
+        gs.ecs.register::<MyTurn>();
+        gs.ecs.register::<SpeedBonus>();
+        gs.ecs.register::<SimpleMarker<SerializeMe>>();
+        gs.ecs.register::<SerializationHelper>();
+
+        gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        gs.ecs.insert(raws::load_raws());
+        gs.ecs.insert(record_paths::RecordPaths::default());
+
+        gs
+    }
+
+    pub fn run_systems(&mut self) {
+        let mut turns = turn_system::TurnSystem {};
+        let mut statuses = status_effect_system::StatusEffectSystem {};
+        let mut vis = VisibilitySystem {};
+        let mut monster = MonsterAI {};
+        let mut map_indexing = MapIndexingSystem {};
+        let mut triggers = trigger_system::TriggerSystem {};
+        let mut melee = MeleeCombatSystem {};
+        let mut hunger = hunger_system::HungerSystem {};
+        let mut damage = DamageSystem {};
+        let mut pickup = ItemCollectionSystem {};
+        let mut drop_items = ItemDropSystem {};
+        let mut potions = ItemUseSystem {};
+        let mut remove_items = ItemRemoveSystem {};
+
+        turns.run_now(&self.ecs);
+        statuses.run_now(&self.ecs);
+        vis.run_now(&self.ecs);
+        monster.run_now(&self.ecs);
+        map_indexing.run_now(&self.ecs);
+        triggers.run_now(&self.ecs);
+        melee.run_now(&self.ecs);
+        hunger.run_now(&self.ecs);
+        damage.run_now(&self.ecs);
+        pickup.run_now(&self.ecs);
+        drop_items.run_now(&self.ecs);
+        potions.run_now(&self.ecs);
+        remove_items.run_now(&self.ecs);
+
+        self.ecs.maintain();
+    }
+
+    /// Populates the world with a fresh first level, the player and their starting resources.
+    pub fn new_game(&mut self) {
+        let seed = self.requested_seed.unwrap_or_else(seed::random_seed);
+        self.ecs.insert(GameSeed { seed });
+        self.ecs.insert(RandomNumberGenerator::seeded(seed));
+        let start = self.generate_level(1);
+
+        let player_entity = spawner::player(&mut self.ecs, start.x, start.y);
+
+        self.ecs.insert(Point::new(start.x, start.y));
+        self.ecs.insert(player_entity);
+        self.ecs.insert(gamelog::GameLog {
+            entries: vec!["Welcome to Rusty Roguelike".to_string()],
+        });
+        self.ecs.insert(run_stats::RunStats::default());
+    }
+
+    /// Builds level `depth` with the requested builder, or a random one, stores its map and
+    /// spawns its contents. Returns where the player starts.
+    fn generate_level(&mut self, depth: i32) -> Position {
+        let mut builder = {
+            let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
+            let mut builder = self
+                .requested_builder
+                .as_deref()
+                .and_then(|name| {
+                    map_builders::builder_by_name(name, depth, LEVEL_WIDTH, LEVEL_HEIGHT)
+                })
+                .unwrap_or_else(|| {
+                    map_builders::random_builder(depth, LEVEL_WIDTH, LEVEL_HEIGHT, &mut rng)
+                });
+            builder.build_map(&mut rng);
+            builder
+        };
+
+        self.mapgen_history = builder.get_snapshot_history();
+        self.ecs.insert(builder.get_map());
+        builder.spawn_entities(&mut self.ecs);
+        builder.get_starting_position()
+    }
+
+    /// Plays back how the current level was generated first if `show_mapgen` is set, then
+    /// carries on to `next`.
+    pub fn show_mapgen_then(&mut self, next: RunState) -> RunState {
+        if !self.show_mapgen || self.mapgen_history.is_empty() {
+            return next;
+        }
+        self.mapgen_index = 0;
+        self.mapgen_timer = 0.0;
+        self.mapgen_next_state = next;
+        RunState::MapGeneration
+    }
+
+    fn game_over_cleanup(&mut self) {
+        // Delete everything
+        let mut to_delete = Vec::new();
+        for e in self.ecs.entities().join() {
+            to_delete.push(e);
+        }
+        for del in to_delete.iter() {
+            self.ecs.delete_entity(*del).expect("Deletion failed");
+        }
+        self.ecs.maintain();
+
+        self.new_game();
+    }
+
+    fn entities_to_remove_on_level_change(&mut self) -> Vec<Entity> {
+        let entities = self.ecs.entities();
+        let player = self.ecs.read_storage::<Player>();
+        let backpack = self.ecs.read_storage::<InBackpack>();
+        let equipped = self.ecs.read_storage::<Equipped>();
+        let statuses = self.ecs.read_storage::<StatusEffect>();
+        let player_entity = self.ecs.fetch::<Entity>();
+
+        let mut to_delete: Vec<Entity> = Vec::new();
+        for entity in entities.join() {
+            if player.get(entity).is_some() {
+                continue;
+            }
+
+            if let Some(bp) = backpack.get(entity) {
+                if bp.owner == *player_entity {
+                    continue;
+                }
+            }
+
+            if let Some(eq) = equipped.get(entity) {
+                if eq.owner == *player_entity {
+                    continue;
+                }
+            }
+
+            if let Some(status) = statuses.get(entity) {
+                if status.target == *player_entity {
+                    continue;
+                }
+            }
+
+            to_delete.push(entity);
+        }
+
+        to_delete
+    }
+
+    fn goto_next_level(&mut self) {
+        // Delete entities that aren't the player or their belongings
+        let to_delete = self.entities_to_remove_on_level_change();
+        for target in to_delete {
+            self.ecs
+                .delete_entity(target)
+                .expect("Unable to delete entity");
+        }
+
+        // Build a new map and place the player
+        let new_depth = self.ecs.fetch::<Map>().depth + 1;
+        let start = self.generate_level(new_depth);
+
+        let mut player_position = self.ecs.write_resource::<Point>();
+        *player_position = Point::new(start.x, start.y);
+        let mut position_components = self.ecs.write_storage::<Position>();
+        let player_entity = self.ecs.fetch::<Entity>();
+        if let Some(player_pos_comp) = position_components.get_mut(*player_entity) {
+            player_pos_comp.x = start.x;
+            player_pos_comp.y = start.y;
+        }
+
+        // Mark the player's visibility as dirty
+        let mut viewshed_components = self.ecs.write_storage::<Viewshed>();
+        if let Some(vs) = viewshed_components.get_mut(*player_entity) {
+            vs.dirty = true;
+        }
+
+        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+        gamelog.entries.push(format!(
+            "You descend to depth {} of the dungeon.",
+            new_depth
+        ));
+    }
+
+    /// Writes the game to the save file, to resume at the input prompt when it is continued.
+    pub fn save_game(&mut self) {
+        *self.ecs.write_resource::<RunState>() = RunState::AwaitingInput;
+        saveload_system::save_game(&mut self.ecs);
+    }
+
+    /// Loads the saved game in place of the current one and removes the save, returning the
+    /// state to resume in.
+    pub fn continue_game(&mut self) -> RunState {
+        saveload_system::load_game(&mut self.ecs);
+        saveload_system::delete_save(&self.ecs.fetch::<record_paths::RecordPaths>());
+        // The tile content index isn't saved, so rebuild it before play resumes
+        let mut map_indexing = MapIndexingSystem {};
+        map_indexing.run_now(&self.ecs);
+        *self.ecs.fetch::<RunState>()
+    }
+
+    /// Runs one of the states that need no player input and returns the state that follows it.
+    pub fn advance(&mut self, runstate: RunState) -> RunState {
+        match runstate {
+            RunState::PreRun => {
+                self.run_systems();
+                self.ecs.maintain();
+                RunState::Ticking
+            }
+            RunState::PlayerTurn => {
+                self.run_systems();
+                self.ecs.maintain();
+                RunState::Ticking
+            }
+            RunState::Ticking => {
+                // Tick until the player has the energy to act again, letting monsters act
+                // whenever they do
+                loop {
+                    self.run_systems();
+                    self.ecs.maintain();
+                    delete_the_dead(&mut self.ecs, !self.replay.is_playing());
+                    let runstate = *self.ecs.fetch::<RunState>();
+                    if runstate != RunState::Ticking {
+                        return runstate;
+                    }
+                }
+            }
+            RunState::NextLevel => {
+                self.goto_next_level();
+                self.show_mapgen_then(RunState::PreRun)
+            }
+            _ => runstate,
+        }
+    }
+
+    /// Advances the game by one frame: renders, then runs whatever the current `RunState` calls for.
+    fn step(&mut self, ctx: &mut Rltk) {
+        let mut newrunstate;
+        {
+            let runstate = self.ecs.fetch::<RunState>();
+            newrunstate = *runstate;
+        }
+
+        ctx.cls();
+
+        match newrunstate {
+            RunState::MainMenu { .. }
+            | RunState::GameOver
+            | RunState::ShowHighScores
+            | RunState::MapGeneration => {}
+            _ => {
+                // rendering
+                camera::render_camera(&self.ecs, ctx);
+                draw_ui(&self.ecs, ctx);
+                draw_tooltips(&self.ecs, ctx);
+            }
+        }
+
+        match newrunstate {
+            RunState::PreRun | RunState::PlayerTurn | RunState::Ticking | RunState::NextLevel => {
+                newrunstate = self.advance(newrunstate);
+            }
+            RunState::AwaitingInput => {
+                newrunstate = player_input(self, ctx.key);
+            }
+            RunState::ShowInventory => {
+                let result = gui::show_inventory(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let item_entity = result.1.unwrap();
+                        let is_ranged = self.ecs.read_storage::<Ranged>();
+                        let is_item_ranged = is_ranged.get(item_entity);
+                        if let Some(is_item_ranged) = is_item_ranged {
+                            newrunstate = RunState::ShowTargeting {
+                                range: is_item_ranged.range,
+                                item: item_entity,
+                            }
+                        } else {
+                            let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                            intent
+                                .insert(
+                                    *self.ecs.fetch::<Entity>(),
+                                    WantsToUseItem {
+                                        item: item_entity,
+                                        target: None,
+                                    },
+                                )
+                                .expect("Unable to insert intent");
+                            newrunstate = RunState::PlayerTurn;
+                        }
+                    }
+                }
+            }
+            RunState::ShowDropItem => {
+                let result = gui::drop_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let item_entity = result.1.unwrap();
+                        let mut intent = self.ecs.write_storage::<WantsToDropItem>();
+                        intent
+                            .insert(
+                                *self.ecs.fetch::<Entity>(),
+                                WantsToDropItem { item: item_entity },
+                            )
+                            .expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::LevelUp => {
+                if let gui::LevelUpResult::Selected(stat) = gui::level_up_menu(ctx) {
+                    improve_stat(&mut self.ecs, stat);
+                    newrunstate = RunState::AwaitingInput;
+                }
+            }
+            RunState::ShowRemoveItem => {
+                let result = gui::remove_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let item_entity = result.1.unwrap();
+                        let mut intent = self.ecs.write_storage::<WantsToRemoveItem>();
+                        intent
+                            .insert(
+                                *self.ecs.fetch::<Entity>(),
+                                WantsToRemoveItem { item: item_entity },
+                            )
+                            .expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowTargeting { range, item } => {
+                let result = gui::ranged_target(self, ctx, range);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                        intent
+                            .insert(
+                                *self.ecs.fetch::<Entity>(),
+                                WantsToUseItem {
+                                    item,
+                                    target: result.1,
+                                },
+                            )
+                            .expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::MainMenu { .. } => {
+                let result = gui::main_menu(self, ctx);
+                match result {
+                    gui::MainMenuResult::NoSelection { selected } => {
+                        newrunstate = RunState::MainMenu {
+                            menu_selection: selected,
+                        }
+                    }
+                    gui::MainMenuResult::Selected { selected } => match selected {
+                        gui::MainMenuSelection::NewGame => {
+                            let seed = self.ecs.fetch::<GameSeed>().seed;
+                            self.replay
+                                .start_recording(seed, self.requested_builder.clone());
+                            newrunstate = self.show_mapgen_then(RunState::PreRun)
+                        }
+                        gui::MainMenuSelection::Continue => {
+                            newrunstate = self.continue_game();
+                        }
+                        gui::MainMenuSelection::HighScores => {
+                            newrunstate = RunState::ShowHighScores
+                        }
+                        gui::MainMenuSelection::Quit => {
+                            ::std::process::exit(0);
+                        }
+                    },
+                }
+            }
+            RunState::GameOver => {
+                let result = gui::game_over(&self.ecs, ctx);
+                match result {
+                    gui::GameOverResult::NoSelection => {}
+                    gui::GameOverResult::QuitToMenu => {
+                        self.replay.finish_recording();
+                        self.game_over_cleanup();
+                        newrunstate = RunState::ShowHighScores;
+                    }
+                }
+            }
+            RunState::ShowHighScores => {
+                if gui::high_scores(&self.ecs, ctx) == gui::HighScoresResult::Done {
+                    newrunstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::NewGame,
+                    };
+                }
+            }
+            RunState::MapGeneration => {
+                let snapshot = &self.mapgen_history[self.mapgen_index];
+                let center = Point::new(snapshot.width / 2, snapshot.height / 2);
+                draw_map(snapshot, camera::camera_origin(snapshot, center), ctx);
+
+                // Any key skips the rest of the playback
+                self.mapgen_timer += ctx.frame_time_ms;
+                if self.mapgen_timer > MAPGEN_FRAME_MS {
+                    self.mapgen_timer = 0.0;
+                    self.mapgen_index += 1;
+                }
+                if ctx.key.is_some() || self.mapgen_index >= self.mapgen_history.len() {
+                    newrunstate = self.mapgen_next_state;
+                }
+            }
+            RunState::SaveGame => {
+                // A replayed Escape mustn't overwrite the player's own save
+                if !self.replay.is_playing() {
+                    self.save_game();
+                }
+                ctx.quit();
+                newrunstate = RunState::AwaitingInput;
+            }
+        }
+
+        {
+            let mut runwriter = self.ecs.write_resource::<RunState>();
+            *runwriter = newrunstate;
+        }
+
+        delete_the_dead(&mut self.ecs, !self.replay.is_playing());
+    }
+}
+impl GameState for State {
+    fn tick(&mut self, ctx: &mut Rltk) {
+        for _ in 0..self.replay.steps_per_tick() {
+            let runstate = *self.ecs.fetch::<RunState>();
+            self.replay.feed_input(runstate, ctx);
+            self.step(ctx);
+            self.replay.record_input(runstate, ctx);
+        }
+    }
+}
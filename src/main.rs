@@ -1,232 +1,41 @@
-use rltk::{GameState, Point, RandomNumberGenerator, Rltk, RGB};
-use specs::{prelude::*, World};
-use std::cmp::{max, min};
-mod components;
-use components::*;
-mod player;
-use player::*;
-mod map;
-use map::*;
-mod rect;
-use rect::*;
-mod visibility_system;
-use visibility_system::*;
-mod monster_ai_system;
-use monster_ai_system::*;
-mod map_indexing_system;
-use map_indexing_system::*;
-mod damage_system;
-use damage_system::*;
-mod melee_combat_system;
-use melee_combat_system::*;
-mod gui;
-use gui::*;
-mod inventory_system;
-use inventory_system::*;
-
-use crate::spawner::spawn_room;
-mod gamelog;
-mod spawner;
-
-pub struct State {
-    pub ecs: World,
-}
-
-impl State {
-    fn run_systems(&mut self) {
-        let mut vis = VisibilitySystem {};
-        let mut monster = MonsterAI {};
-        let mut map_indexing = MapIndexingSystem {};
-        let mut melee = MeleeCombatSystem {};
-        let mut damage = DamageSystem {};
-        let mut pickup = ItemCollectionSystem {};
-        let mut drop_items = ItemDropSystem {};
-        let mut potions = ItemUseSystem {};
-
-        vis.run_now(&self.ecs);
-        monster.run_now(&self.ecs);
-        map_indexing.run_now(&self.ecs);
-        melee.run_now(&self.ecs);
-        damage.run_now(&self.ecs);
-        pickup.run_now(&self.ecs);
-        drop_items.run_now(&self.ecs);
-        potions.run_now(&self.ecs);
-
-        self.ecs.maintain();
-    }
-}
-impl GameState for State {
-    fn tick(&mut self, ctx: &mut Rltk) {
-        ctx.cls();
-        // rendering
-        draw_map(&self.ecs, ctx);
-
-        let mut newrunstate;
-        {
-            let runstate = self.ecs.fetch::<RunState>();
-            newrunstate = *runstate;
-        }
-
-        match newrunstate {
-            RunState::PreRun => {
-                self.run_systems();
-                self.ecs.maintain();
-                newrunstate = RunState::AwaitingInput;
-            }
-            RunState::AwaitingInput => {
-                newrunstate = player_input(self, ctx);
-            }
-            RunState::PlayerTurn => {
-                self.run_systems();
-                self.ecs.maintain();
-                newrunstate = RunState::MonsterTurn;
-            }
-            RunState::MonsterTurn => {
-                self.run_systems();
-                self.ecs.maintain();
-                newrunstate = RunState::AwaitingInput;
-            }
-            RunState::ShowInventory => {
-                let result = gui::show_inventory(self, ctx);
-                match result.0 {
-                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
-                    gui::ItemMenuResult::NoResponse => {}
-                    gui::ItemMenuResult::Selected => {
-                        let item_entity = result.1.unwrap();
-                        let is_ranged = self.ecs.read_storage::<Ranged>();
-                        let is_item_ranged = is_ranged.get(item_entity);
-                        if let Some(is_item_ranged) = is_item_ranged {
-                            newrunstate = RunState::ShowTargeting {
-                                range: is_item_ranged.range,
-                                item: item_entity,
-                            }
-                        } else {
-                            let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                            intent
-                                .insert(
-                                    *self.ecs.fetch::<Entity>(),
-                                    WantsToUseItem {
-                                        item: item_entity,
-                                        target: None,
-                                    },
-                                )
-                                .expect("Unable to insert intent");
-                            newrunstate = RunState::PlayerTurn;
-                        }
-                    }
-                }
-            }
-            RunState::ShowDropItem => {
-                let result = gui::drop_item_menu(self, ctx);
-                match result.0 {
-                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
-                    gui::ItemMenuResult::NoResponse => {}
-                    gui::ItemMenuResult::Selected => {
-                        let item_entity = result.1.unwrap();
-                        let mut intent = self.ecs.write_storage::<WantsToDropItem>();
-                        intent
-                            .insert(
-                                *self.ecs.fetch::<Entity>(),
-                                WantsToDropItem { item: item_entity },
-                            )
-                            .expect("Unable to insert intent");
-                        newrunstate = RunState::PlayerTurn;
-                    }
-                }
-            }
-            RunState::ShowTargeting { range, item } => {
-                let result = gui::ranged_target(self, ctx, range);
-                match result.0 {
-                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
-                    gui::ItemMenuResult::NoResponse => {}
-                    gui::ItemMenuResult::Selected => {
-                        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                        intent
-                            .insert(
-                                *self.ecs.fetch::<Entity>(),
-                                WantsToUseItem {
-                                    item,
-                                    target: result.1,
-                                },
-                            )
-                            .expect("Unable to insert intent");
-                        newrunstate = RunState::PlayerTurn;
-                    }
-                }
-            }
-        }
-
-        {
-            let mut runwriter = self.ecs.write_resource::<RunState>();
-            *runwriter = newrunstate;
-        }
-
-        delete_the_dead(&mut self.ecs);
-
-        let positions = self.ecs.read_storage::<Position>();
-        let renderables = self.ecs.read_storage::<Renderable>();
-        let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
-        data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
-        let map = self.ecs.fetch::<Map>();
-
-        for (pos, render) in data.iter() {
-            let idx = map.xy_idx(pos.x, pos.y);
-            if map.visible_tiles[idx] {
-                ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
-            }
-        }
-
-        draw_ui(&self.ecs, ctx);
-        draw_tooltips(&self.ecs, ctx);
-    }
+use rogue_rs::args::arg_flag;
+use rogue_rs::components::RunState;
+use rogue_rs::map_builders::builder_from_args;
+use rogue_rs::replay::ReplayMode;
+use rogue_rs::seed::seed_from_args;
+use rogue_rs::{gui, State};
+
+/// Bad command-line arguments stop the game before it opens a window, rather than being ignored.
+fn or_exit<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        std::process::exit(2);
+    })
 }
 
 fn main() -> rltk::BError {
     use rltk::RltkBuilder;
-    let mut context = RltkBuilder::simple80x50().with_title("rogue-rs").build()?;
+    let replay = or_exit(ReplayMode::from_args());
+    // A replay has to use the seed and builder it was recorded with, even if that was none
+    let (seed, builder) = if replay.is_playing() {
+        (replay.seed(), replay.builder())
+    } else {
+        (or_exit(seed_from_args()), or_exit(builder_from_args()))
+    };
+    let context = RltkBuilder::simple80x50().with_title("rogue-rs").build()?;
     // context.with_post_scanlines(true);
-    let mut gs = State { ecs: World::new() };
-
-    gs.ecs.register::<Position>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<LeftMover>();
-    gs.ecs.register::<Player>();
-    gs.ecs.register::<Viewshed>();
-    gs.ecs.register::<Monster>();
-    gs.ecs.register::<Name>();
-    gs.ecs.register::<BlocksTile>();
-    gs.ecs.register::<CombatStats>();
-    gs.ecs.register::<WantsToMelee>();
-    gs.ecs.register::<SufferDamage>();
-    gs.ecs.register::<Item>();
-    gs.ecs.register::<Consumable>();
-    gs.ecs.register::<ProvidesHealing>();
-    gs.ecs.register::<InBackpack>();
-    gs.ecs.register::<WantsToPickupItem>();
-    gs.ecs.register::<WantsToDropItem>();
-    gs.ecs.register::<WantsToUseItem>();
-    gs.ecs.register::<Ranged>();
-    gs.ecs.register::<InflictsDamage>();
-    gs.ecs.register::<AreaOfEffect>();
-    gs.ecs.register::<Confusion>();
-
-    gs.ecs.insert(RandomNumberGenerator::new());
-    let map: Map = Map::new_map_rooms_and_corridors();
-    let (player_x, player_y) = map.rooms[0].center();
-
-    let player_entity = spawner::player(&mut gs.ecs, player_x, player_y);
-
-    for room in map.rooms.iter().skip(1) {
-        spawn_room(&mut gs.ecs, room);
+    let mut gs = State::new(seed, builder, replay);
+    gs.show_mapgen = arg_flag("--show-mapgen");
+
+    gs.new_game();
+    if gs.replay.is_playing() {
+        let runstate = gs.show_mapgen_then(RunState::PreRun);
+        gs.ecs.insert(runstate);
+    } else {
+        gs.ecs.insert(RunState::MainMenu {
+            menu_selection: gui::MainMenuSelection::NewGame,
+        });
     }
 
-    gs.ecs.insert(map);
-    gs.ecs.insert(Point::new(player_x, player_y));
-    gs.ecs.insert(player_entity);
-    gs.ecs.insert(RunState::PreRun);
-    gs.ecs.insert(gamelog::GameLog {
-        entries: vec!["Welcome to Rusty Roguelike".to_string()],
-    });
-
     rltk::main_loop(context, gs)
 }
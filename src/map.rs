@@ -1,60 +1,61 @@
-use super::Rect;
-use rltk::{Algorithm2D, BaseMap, Point, RandomNumberGenerator, Rltk, RGB};
+use super::camera::{VIEW_HEIGHT, VIEW_WIDTH};
+use rltk::{Algorithm2D, BaseMap, Point, Rltk, RGB};
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
-use std::cmp::{max, min};
+use std::collections::HashSet;
 
-pub const MAP_WIDTH: usize = 80;
-pub const MAP_HEIGHT: usize = 43;
-pub const MAP_SIZE: usize = MAP_HEIGHT * MAP_WIDTH;
-
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
+    DownStairs,
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize, Clone)]
 pub struct Map {
     pub tiles: Vec<TileType>,
-    pub rooms: Vec<Rect>,
     pub width: i32,
     pub height: i32,
     pub revealed_tiles: Vec<bool>,
     pub visible_tiles: Vec<bool>,
     pub blocked: Vec<bool>,
+
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
     pub tile_content: Vec<Vec<Entity>>,
+    /// Tiles holding a closed door. They are blocked and opaque, but monsters still path through
+    /// them, opening the door on the way.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub closed_doors: HashSet<usize>,
+    /// Set when a door opens or closes, so every viewshed is recomputed on the next turn.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub view_blocked_changed: bool,
+    pub depth: i32,
 }
 
 impl Map {
-    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
-        (y as usize * self.width as usize) + x as usize
-    }
-
-    fn apply_room_to_map(&mut self, room: &Rect) {
-        for y in room.y1 + 1..=room.y2 {
-            for x in room.x1 + 1..=room.x2 {
-                let idx = self.xy_idx(x, y);
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
-    }
-
-    fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
-        for x in min(x1, x2)..=max(x1, x2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx as usize] = TileType::Floor;
-            }
+    /// A `width` by `height` map of solid wall for level `new_depth`, ready for a `MapBuilder`
+    /// to carve out.
+    pub fn new(new_depth: i32, width: i32, height: i32) -> Map {
+        let map_size = (width * height) as usize;
+        Map {
+            tiles: vec![TileType::Wall; map_size],
+            width,
+            height,
+            revealed_tiles: vec![false; map_size],
+            visible_tiles: vec![false; map_size],
+            blocked: vec![false; map_size],
+            tile_content: vec![Vec::new(); map_size],
+            closed_doors: HashSet::new(),
+            view_blocked_changed: false,
+            depth: new_depth,
         }
     }
 
-    fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
-        for y in min(y1, y2)..=max(y1, y2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx as usize] = TileType::Floor;
-            }
-        }
+    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
+        (y as usize * self.width as usize) + x as usize
     }
 
     fn is_exit_valid(&self, x: i32, y: i32) -> bool {
@@ -62,7 +63,7 @@ impl Map {
             return false;
         }
         let idx = self.xy_idx(x, y);
-        !self.blocked[idx]
+        !self.blocked[idx] || self.closed_doors.contains(&idx)
     }
 
     pub fn populate_blocked(&mut self) {
@@ -76,65 +77,11 @@ impl Map {
             content.clear();
         }
     }
-
-    /// Makes a new map using the algorithm from http://rogueliketutorials.com/tutorials/tcod/part-3/
-    /// This gives a handful of random rooms and corridors joining them together.
-    pub fn new_map_rooms_and_corridors() -> Map {
-        let mut map = Map {
-            tiles: vec![TileType::Wall; MAP_SIZE],
-            rooms: Vec::new(),
-            width: MAP_WIDTH as i32,
-            height: MAP_HEIGHT as i32,
-            revealed_tiles: vec![false; MAP_SIZE],
-            visible_tiles: vec![false; MAP_SIZE],
-            blocked: vec![false; MAP_SIZE],
-            tile_content: vec![Vec::new(); MAP_SIZE],
-        };
-
-        const MAX_ROOMS: i32 = 30;
-        const MIN_SIZE: i32 = 6;
-        const MAX_SIZE: i32 = 10;
-
-        let mut rng = RandomNumberGenerator::new();
-
-        for _i in 0..MAX_ROOMS {
-            let w = rng.range(MIN_SIZE, MAX_SIZE);
-            let h = rng.range(MIN_SIZE, MAX_SIZE);
-            let x = rng.roll_dice(1, map.width - w - 1) - 1;
-            let y = rng.roll_dice(1, map.height - h - 1) - 1;
-            let new_room = Rect::new(x, y, w, h);
-            let mut ok = true;
-            for other_room in map.rooms.iter() {
-                if new_room.intersect(other_room) {
-                    ok = false
-                }
-            }
-            if ok {
-                map.apply_room_to_map(&new_room);
-
-                if !map.rooms.is_empty() {
-                    let (new_x, new_y) = new_room.center();
-                    let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
-                    if rng.range(0, 2) == 1 {
-                        map.apply_horizontal_tunnel(prev_x, new_x, prev_y);
-                        map.apply_vertical_tunnel(prev_y, new_y, new_x);
-                    } else {
-                        map.apply_vertical_tunnel(prev_y, new_y, prev_x);
-                        map.apply_horizontal_tunnel(prev_x, new_x, new_y);
-                    }
-                }
-
-                map.rooms.push(new_room);
-            }
-        }
-
-        map
-    }
 }
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx] == TileType::Wall
+        self.tiles[idx] == TileType::Wall || self.closed_doors.contains(&idx)
     }
 
     fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
@@ -188,38 +135,40 @@ impl Algorithm2D for Map {
     }
 }
 
-pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
-    let map = ecs.fetch::<Map>();
-
-    let mut y = 0;
-    let mut x = 0;
-    for (idx, tile) in map.tiles.iter().enumerate() {
-        // Render a tile depending upon the tile type
-
-        if map.revealed_tiles[idx] {
-            let glyph;
-            let mut fg;
-            match tile {
-                TileType::Floor => {
-                    glyph = rltk::to_cp437('.');
-                    fg = RGB::from_f32(0.0, 0.5, 0.5);
+/// Draws the tiles of `map` that fill the screen from `origin`, its tile in the top left corner.
+pub fn draw_map(map: &Map, origin: Point, ctx: &mut Rltk) {
+    for screen_y in 0..VIEW_HEIGHT {
+        for screen_x in 0..VIEW_WIDTH {
+            let x = origin.x + screen_x;
+            let y = origin.y + screen_y;
+            if x < 0 || y < 0 || x >= map.width || y >= map.height {
+                continue;
+            }
+
+            // Render a tile depending upon the tile type
+            let idx = map.xy_idx(x, y);
+            if map.revealed_tiles[idx] {
+                let glyph;
+                let mut fg;
+                match map.tiles[idx] {
+                    TileType::Floor => {
+                        glyph = rltk::to_cp437('.');
+                        fg = RGB::from_f32(0.0, 0.5, 0.5);
+                    }
+                    TileType::Wall => {
+                        glyph = rltk::to_cp437('#');
+                        fg = RGB::from_f32(0., 1.0, 0.);
+                    }
+                    TileType::DownStairs => {
+                        glyph = rltk::to_cp437('>');
+                        fg = RGB::from_f32(0., 1.0, 1.0);
+                    }
                 }
-                TileType::Wall => {
-                    glyph = rltk::to_cp437('#');
-                    fg = RGB::from_f32(0., 1.0, 0.);
+                if !map.visible_tiles[idx] {
+                    fg = fg.to_greyscale()
                 }
+                ctx.set(screen_x, screen_y, fg, RGB::from_f32(0., 0., 0.), glyph);
             }
-            if !map.visible_tiles[idx] {
-                fg = fg.to_greyscale()
-            }
-            ctx.set(x, y, fg, RGB::from_f32(0., 0., 0.), glyph);
-        }
-
-        // Move the coordinates
-        x += 1;
-        if x > MAP_WIDTH as i32 - 1 {
-            x = 0;
-            y += 1;
         }
     }
 }
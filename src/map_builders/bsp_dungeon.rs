@@ -0,0 +1,184 @@
+use super::common::{apply_room_to_map, snapshot, spawn_doors};
+use super::MapBuilder;
+use crate::{spawner, Map, Position, Rect, TileType};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+/// How many times to try placing a room before settling for what fits.
+const ROOM_ATTEMPTS: i32 = 240;
+
+/// Binary space partitioning: keeps splitting the map into quarters and drops a room into the
+/// pieces, which packs rooms tightly without overlaps. Rooms are joined left to right.
+pub struct BspDungeonBuilder {
+    map: Map,
+    starting_position: Position,
+    history: Vec<Map>,
+    rooms: Vec<Rect>,
+    rects: Vec<Rect>,
+}
+
+impl BspDungeonBuilder {
+    pub fn new(depth: i32, width: i32, height: i32) -> BspDungeonBuilder {
+        BspDungeonBuilder {
+            map: Map::new(depth, width, height),
+            starting_position: Position { x: 0, y: 0 },
+            history: Vec::new(),
+            rooms: Vec::new(),
+            rects: Vec::new(),
+        }
+    }
+
+    /// Splits `rect` into four quarters, each a candidate for a room or a further split.
+    fn add_subrects(&mut self, rect: Rect) {
+        let half_width = i32::max((rect.x2 - rect.x1) / 2, 1);
+        let half_height = i32::max((rect.y2 - rect.y1) / 2, 1);
+
+        self.rects
+            .push(Rect::new(rect.x1, rect.y1, half_width, half_height));
+        self.rects.push(Rect::new(
+            rect.x1,
+            rect.y1 + half_height,
+            half_width,
+            half_height,
+        ));
+        self.rects.push(Rect::new(
+            rect.x1 + half_width,
+            rect.y1,
+            half_width,
+            half_height,
+        ));
+        self.rects.push(Rect::new(
+            rect.x1 + half_width,
+            rect.y1 + half_height,
+            half_width,
+            half_height,
+        ));
+    }
+
+    fn get_random_rect(&self, rng: &mut RandomNumberGenerator) -> Rect {
+        let idx = rng.roll_dice(1, self.rects.len() as i32) - 1;
+        self.rects[idx as usize]
+    }
+
+    /// A room of at least 3x3, somewhere near the top left of `rect`.
+    fn get_random_sub_rect(&self, rect: Rect, rng: &mut RandomNumberGenerator) -> Rect {
+        let mut result = rect;
+        let rect_width = i32::abs(rect.x1 - rect.x2);
+        let rect_height = i32::abs(rect.y1 - rect.y2);
+
+        let w = i32::max(3, rng.roll_dice(1, i32::min(rect_width, 10)) - 1) + 1;
+        let h = i32::max(3, rng.roll_dice(1, i32::min(rect_height, 10)) - 1) + 1;
+
+        result.x1 += rng.roll_dice(1, 6) - 1;
+        result.y1 += rng.roll_dice(1, 6) - 1;
+        result.x2 = result.x1 + w;
+        result.y2 = result.y1 + h;
+
+        result
+    }
+
+    /// Whether `rect` lies inside the map with a two tile gap of solid wall all around it.
+    fn is_possible(&self, rect: Rect) -> bool {
+        for y in rect.y1 - 2..=rect.y2 + 2 {
+            for x in rect.x1 - 2..=rect.x2 + 2 {
+                if x < 1 || y < 1 || x > self.map.width - 2 || y > self.map.height - 2 {
+                    return false;
+                }
+                let idx = self.map.xy_idx(x, y);
+                if self.map.tiles[idx] != TileType::Wall {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Digs an L-shaped corridor, first across and then down or up.
+    fn draw_corridor(&mut self, x1: i32, y1: i32, x2: i32, y2: i32) {
+        let mut x = x1;
+        let mut y = y1;
+
+        while x != x2 || y != y2 {
+            if x < x2 {
+                x += 1;
+            } else if x > x2 {
+                x -= 1;
+            } else if y < y2 {
+                y += 1;
+            } else if y > y2 {
+                y -= 1;
+            }
+
+            let idx = self.map.xy_idx(x, y);
+            self.map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+impl MapBuilder for BspDungeonBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let whole_map = Rect::new(2, 2, self.map.width - 5, self.map.height - 5);
+        self.rects.push(whole_map);
+        self.add_subrects(whole_map);
+
+        for _i in 0..ROOM_ATTEMPTS {
+            let rect = self.get_random_rect(rng);
+            let candidate = self.get_random_sub_rect(rect, rng);
+
+            if self.is_possible(candidate) {
+                apply_room_to_map(&mut self.map, &candidate);
+                self.rooms.push(candidate);
+                self.add_subrects(rect);
+                self.take_snapshot();
+            }
+        }
+
+        self.rooms.sort_by_key(|room| room.x1);
+
+        // Join each room to its neighbour on the right, from a random spot in each
+        for i in 0..self.rooms.len() - 1 {
+            let room = self.rooms[i];
+            let next_room = self.rooms[i + 1];
+            let start_x = room.x1 + rng.roll_dice(1, room.x2 - room.x1);
+            let start_y = room.y1 + rng.roll_dice(1, room.y2 - room.y1);
+            let end_x = next_room.x1 + rng.roll_dice(1, next_room.x2 - next_room.x1);
+            let end_y = next_room.y1 + rng.roll_dice(1, next_room.y2 - next_room.y1);
+            self.draw_corridor(start_x, start_y, end_x, end_y);
+            self.take_snapshot();
+        }
+
+        let (stairs_x, stairs_y) = self.rooms[self.rooms.len() - 1].center();
+        let stairs_idx = self.map.xy_idx(stairs_x, stairs_y);
+        self.map.tiles[stairs_idx] = TileType::DownStairs;
+        self.take_snapshot();
+
+        let (start_x, start_y) = self.rooms[0].center();
+        self.starting_position = Position {
+            x: start_x,
+            y: start_y,
+        };
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        spawn_doors(ecs, &self.rooms);
+        for room in self.rooms.iter().skip(1) {
+            spawner::spawn_room(ecs, room, self.map.depth);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.clone()
+    }
+
+    fn take_snapshot(&mut self) {
+        self.history.push(snapshot(&self.map));
+    }
+}
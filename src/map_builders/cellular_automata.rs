@@ -0,0 +1,129 @@
+use super::common::{
+    generate_voronoi_spawn_regions, largest_floor_region,
+    remove_unreachable_areas_returning_most_distant, snapshot, spawn_regions,
+};
+use super::MapBuilder;
+use crate::{Map, Position, TileType};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use std::collections::BTreeMap;
+
+/// Percentage of the map that starts out as floor.
+const FLOOR_CHANCE: i32 = 45;
+/// Smoothing passes run over the starting noise.
+const ITERATIONS: i32 = 15;
+
+/// Natural-looking caves: random noise smoothed by a cellular automaton until walls clump
+/// together and the floor opens into caverns.
+pub struct CellularAutomataBuilder {
+    map: Map,
+    starting_position: Position,
+    history: Vec<Map>,
+    noise_areas: BTreeMap<i32, Vec<usize>>,
+}
+
+impl CellularAutomataBuilder {
+    pub fn new(depth: i32, width: i32, height: i32) -> CellularAutomataBuilder {
+        CellularAutomataBuilder {
+            map: Map::new(depth, width, height),
+            starting_position: Position { x: 0, y: 0 },
+            history: Vec::new(),
+            noise_areas: BTreeMap::new(),
+        }
+    }
+}
+
+impl MapBuilder for CellularAutomataBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let w = self.map.width;
+        let h = self.map.height;
+
+        for y in 1..h - 1 {
+            for x in 1..w - 1 {
+                let idx = self.map.xy_idx(x, y);
+                self.map.tiles[idx] = if rng.roll_dice(1, 100) <= FLOOR_CHANCE {
+                    TileType::Floor
+                } else {
+                    TileType::Wall
+                };
+            }
+        }
+        self.take_snapshot();
+
+        // A tile becomes wall when most of its neighbours are, or when it is cut off entirely
+        let w = w as usize;
+        for _i in 0..ITERATIONS {
+            let mut newtiles = self.map.tiles.clone();
+            for y in 1..h - 1 {
+                for x in 1..self.map.width - 1 {
+                    let idx = self.map.xy_idx(x, y);
+                    let neighbours = [
+                        idx - 1,
+                        idx + 1,
+                        idx - w,
+                        idx + w,
+                        idx - w - 1,
+                        idx - w + 1,
+                        idx + w - 1,
+                        idx + w + 1,
+                    ]
+                    .iter()
+                    .filter(|n| self.map.tiles[**n] == TileType::Wall)
+                    .count();
+
+                    newtiles[idx] = if neighbours > 4 || neighbours == 0 {
+                        TileType::Wall
+                    } else {
+                        TileType::Floor
+                    };
+                }
+            }
+            self.map.tiles = newtiles;
+            self.take_snapshot();
+        }
+
+        // Start on the floor tile closest to the middle of the map, in the largest cave
+        let center = rltk::Point::new(self.map.width / 2, self.map.height / 2);
+        let start_idx = largest_floor_region(&mut self.map)
+            .into_iter()
+            .min_by_key(|idx| {
+                let x = *idx as i32 % self.map.width;
+                let y = *idx as i32 / self.map.width;
+                (x - center.x).pow(2) + (y - center.y).pow(2)
+            })
+            .expect("Cave has no floor");
+        self.starting_position = Position {
+            x: start_idx as i32 % self.map.width,
+            y: start_idx as i32 / self.map.width,
+        };
+
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+        self.take_snapshot();
+
+        self.noise_areas = generate_voronoi_spawn_regions(&self.map, rng);
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        let start_idx = self
+            .map
+            .xy_idx(self.starting_position.x, self.starting_position.y);
+        spawn_regions(ecs, &self.noise_areas, start_idx, self.map.depth);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.clone()
+    }
+
+    fn take_snapshot(&mut self) {
+        self.history.push(snapshot(&self.map));
+    }
+}
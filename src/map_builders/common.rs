@@ -0,0 +1,201 @@
+use crate::{spawner, Map, Position, Rect, TileType};
+use rltk::{BaseMap, RandomNumberGenerator};
+use specs::prelude::*;
+use std::cmp::{max, min};
+use std::collections::BTreeMap;
+
+pub fn apply_room_to_map(map: &mut Map, room: &Rect) {
+    for y in room.y1 + 1..=room.y2 {
+        for x in room.x1 + 1..=room.x2 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+pub fn apply_horizontal_tunnel(map: &mut Map, x1: i32, x2: i32, y: i32) {
+    for x in min(x1, x2)..=max(x1, x2) {
+        let idx = map.xy_idx(x, y);
+        if idx > 0 && idx < map.width as usize * map.height as usize {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+pub fn apply_vertical_tunnel(map: &mut Map, y1: i32, y2: i32, x: i32) {
+    for y in min(y1, y2)..=max(y1, y2) {
+        let idx = map.xy_idx(x, y);
+        if idx > 0 && idx < map.width as usize * map.height as usize {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+/// A copy of `map` with every tile revealed and lit, for playing back how a level was built.
+pub fn snapshot(map: &Map) -> Map {
+    let mut snapshot = map.clone();
+    snapshot.revealed_tiles.fill(true);
+    snapshot.visible_tiles.fill(true);
+    snapshot
+}
+
+/// Walls up every floor tile that can't be walked to from `start_idx`, and returns the reachable
+/// tile furthest from it, which makes a good spot for the stairs down.
+pub fn remove_unreachable_areas_returning_most_distant(map: &mut Map, start_idx: usize) -> usize {
+    map.populate_blocked();
+    let dijkstra_map = rltk::DijkstraMap::new(
+        map.width as usize,
+        map.height as usize,
+        &[start_idx],
+        &*map,
+        1000.0,
+    );
+
+    let mut exit_tile = (start_idx, 0.0f32);
+    for (i, tile) in map.tiles.iter_mut().enumerate() {
+        if *tile == TileType::Floor {
+            let distance_to_start = dijkstra_map.map[i];
+            if distance_to_start == f32::MAX {
+                *tile = TileType::Wall;
+            } else if distance_to_start > exit_tile.1 {
+                exit_tile = (i, distance_to_start);
+            }
+        }
+    }
+    map.populate_blocked();
+
+    exit_tile.0
+}
+
+/// The floor tiles of the biggest area that can be walked around in, so a level doesn't start in
+/// a sealed pocket when its other caves are much larger.
+pub fn largest_floor_region(map: &mut Map) -> Vec<usize> {
+    map.populate_blocked();
+    let mut seen = vec![false; map.tiles.len()];
+    let mut largest: Vec<usize> = Vec::new();
+    for start in 0..map.tiles.len() {
+        if map.tiles[start] != TileType::Floor || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut region = vec![start];
+        let mut next = 0;
+        while next < region.len() {
+            for (exit, _) in map.get_available_exits(region[next]) {
+                if !seen[exit] {
+                    seen[exit] = true;
+                    region.push(exit);
+                }
+            }
+            next += 1;
+        }
+        if region.len() > largest.len() {
+            largest = region;
+        }
+    }
+    largest
+}
+
+/// Moves the stairs down to the reachable tile furthest from `start_idx`, walling up whatever
+/// can no longer be reached, e.g. after a prefab has been stamped over the level.
+pub fn place_stairs_furthest(map: &mut Map, start_idx: usize) {
+    for tile in map.tiles.iter_mut() {
+        if *tile == TileType::DownStairs {
+            *tile = TileType::Floor;
+        }
+    }
+    let exit_idx = remove_unreachable_areas_returning_most_distant(map, start_idx);
+    map.tiles[exit_idx] = TileType::DownStairs;
+}
+
+/// Splits the floor into Voronoi cells from cellular noise, so maps without rooms still get
+/// evenly spread areas to spawn into. Keyed by cell so the order doesn't change between runs.
+pub fn generate_voronoi_spawn_regions(
+    map: &Map,
+    rng: &mut RandomNumberGenerator,
+) -> BTreeMap<i32, Vec<usize>> {
+    let mut noise_areas: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    let mut noise = rltk::FastNoise::seeded(rng.roll_dice(1, 65536) as u64);
+    noise.set_noise_type(rltk::NoiseType::Cellular);
+    noise.set_frequency(0.08);
+    noise.set_cellular_distance_function(rltk::CellularDistanceFunction::Manhattan);
+
+    for y in 1..map.height - 1 {
+        for x in 1..map.width - 1 {
+            let idx = map.xy_idx(x, y);
+            if map.tiles[idx] == TileType::Floor {
+                let cell_value = (noise.get_noise(x as f32, y as f32) * 10240.0) as i32;
+                noise_areas.entry(cell_value).or_default().push(idx);
+            }
+        }
+    }
+
+    noise_areas
+}
+
+/// Spawns into every region but the one holding `start_idx`, so the player doesn't start next to
+/// a monster.
+pub fn spawn_regions(
+    ecs: &mut World,
+    regions: &BTreeMap<i32, Vec<usize>>,
+    start_idx: usize,
+    depth: i32,
+) {
+    for area in regions.values() {
+        if !area.contains(&start_idx) {
+            spawner::spawn_region(ecs, area, depth);
+        }
+    }
+}
+
+/// Where a corridor enters one of `rooms`: a floor tile on the room's edge with wall on either
+/// side of it, so the corridor is a single tile wide there.
+fn doorways(map: &Map, rooms: &[Rect]) -> Vec<usize> {
+    let is_wall = |x: i32, y: i32| map.tiles[map.xy_idx(x, y)] == TileType::Wall;
+    let mut doorways = Vec::new();
+    let mut add = |x: i32, y: i32, across: (i32, i32)| {
+        let idx = map.xy_idx(x, y);
+        if map.tiles[idx] == TileType::Floor
+            && is_wall(x - across.0, y - across.1)
+            && is_wall(x + across.0, y + across.1)
+            && !doorways.contains(&idx)
+        {
+            doorways.push(idx);
+        }
+    };
+
+    for room in rooms {
+        for y in room.y1 + 1..=room.y2 {
+            add(room.x1, y, (0, 1));
+            add(room.x2 + 1, y, (0, 1));
+        }
+        for x in room.x1 + 1..=room.x2 {
+            add(x, room.y1, (1, 0));
+            add(x, room.y2 + 1, (1, 0));
+        }
+    }
+
+    doorways
+}
+
+/// Puts a closed door in every doorway of `rooms`. Reads the map from `ecs`, so prefabs stamped
+/// over the level since the rooms were dug are taken into account.
+pub fn spawn_doors(ecs: &mut World, rooms: &[Rect]) {
+    let free_doorways: Vec<(i32, i32)> = {
+        let map = ecs.fetch::<Map>();
+        let positions = ecs.read_storage::<Position>();
+        let occupied: Vec<usize> = positions
+            .join()
+            .map(|pos| map.xy_idx(pos.x, pos.y))
+            .collect();
+        doorways(&map, rooms)
+            .into_iter()
+            .filter(|idx| !occupied.contains(idx))
+            .map(|idx| (idx as i32 % map.width, idx as i32 / map.width))
+            .collect()
+    };
+
+    for (x, y) in free_doorways {
+        spawner::door(ecs, x, y);
+    }
+}
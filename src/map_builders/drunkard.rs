@@ -0,0 +1,103 @@
+use super::common::{
+    generate_voronoi_spawn_regions, remove_unreachable_areas_returning_most_distant, snapshot,
+    spawn_regions,
+};
+use super::MapBuilder;
+use crate::{Map, Position, TileType};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use std::collections::BTreeMap;
+
+/// Digging stops once this percentage of the map is floor.
+const FLOOR_PERCENT: usize = 40;
+/// Steps each digger takes before giving up.
+const DRUNKARD_LIFETIME: i32 = 400;
+
+/// Drunkard's walk: diggers stumble about at random, clearing whatever they walk through, until
+/// enough of the map is open. Each starts on floor already dug, so the result is one winding cave.
+pub struct DrunkardsWalkBuilder {
+    map: Map,
+    starting_position: Position,
+    history: Vec<Map>,
+    noise_areas: BTreeMap<i32, Vec<usize>>,
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(depth: i32, width: i32, height: i32) -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder {
+            map: Map::new(depth, width, height),
+            starting_position: Position { x: 0, y: 0 },
+            history: Vec::new(),
+            noise_areas: BTreeMap::new(),
+        }
+    }
+}
+
+impl MapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.starting_position = Position {
+            x: self.map.width / 2,
+            y: self.map.height / 2,
+        };
+        let start_idx = self
+            .map
+            .xy_idx(self.starting_position.x, self.starting_position.y);
+        self.map.tiles[start_idx] = TileType::Floor;
+
+        let desired_floor_tiles = self.map.tiles.len() * FLOOR_PERCENT / 100;
+        let mut floor_tiles = vec![start_idx];
+
+        while floor_tiles.len() < desired_floor_tiles {
+            let dig_from = floor_tiles[(rng.roll_dice(1, floor_tiles.len() as i32) - 1) as usize];
+            let mut drunk_x = dig_from as i32 % self.map.width;
+            let mut drunk_y = dig_from as i32 / self.map.width;
+
+            for _step in 0..DRUNKARD_LIFETIME {
+                let drunk_idx = self.map.xy_idx(drunk_x, drunk_y);
+                if self.map.tiles[drunk_idx] == TileType::Wall {
+                    self.map.tiles[drunk_idx] = TileType::Floor;
+                    floor_tiles.push(drunk_idx);
+                }
+
+                // Stay clear of the edge, so the map keeps a solid border
+                match rng.roll_dice(1, 4) {
+                    1 if drunk_x > 2 => drunk_x -= 1,
+                    2 if drunk_x < self.map.width - 2 => drunk_x += 1,
+                    3 if drunk_y > 2 => drunk_y -= 1,
+                    4 if drunk_y < self.map.height - 2 => drunk_y += 1,
+                    _ => {}
+                }
+            }
+            self.take_snapshot();
+        }
+
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+        self.take_snapshot();
+
+        self.noise_areas = generate_voronoi_spawn_regions(&self.map, rng);
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        let start_idx = self
+            .map
+            .xy_idx(self.starting_position.x, self.starting_position.y);
+        spawn_regions(ecs, &self.noise_areas, start_idx, self.map.depth);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.clone()
+    }
+
+    fn take_snapshot(&mut self) {
+        self.history.push(snapshot(&self.map));
+    }
+}
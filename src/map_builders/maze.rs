@@ -0,0 +1,123 @@
+use super::common::{
+    generate_voronoi_spawn_regions, remove_unreachable_areas_returning_most_distant, snapshot,
+    spawn_regions,
+};
+use super::MapBuilder;
+use crate::{Map, Position, TileType};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use std::collections::BTreeMap;
+
+/// Cells dug between snapshots; one per cell would take ages to play back.
+const SNAPSHOT_EVERY: i32 = 10;
+
+/// A maze dug by a recursive backtracker. Cells sit on the odd tiles with walls between them;
+/// the digger knocks through to a random unvisited neighbour, backing up when it runs out.
+pub struct MazeBuilder {
+    map: Map,
+    starting_position: Position,
+    history: Vec<Map>,
+    noise_areas: BTreeMap<i32, Vec<usize>>,
+}
+
+impl MazeBuilder {
+    pub fn new(depth: i32, width: i32, height: i32) -> MazeBuilder {
+        MazeBuilder {
+            map: Map::new(depth, width, height),
+            starting_position: Position { x: 0, y: 0 },
+            history: Vec::new(),
+            noise_areas: BTreeMap::new(),
+        }
+    }
+
+    /// The map tile at the center of maze cell `(cx, cy)`.
+    fn cell_tile(&self, cx: i32, cy: i32) -> usize {
+        self.map.xy_idx(cx * 2 + 1, cy * 2 + 1)
+    }
+}
+
+impl MapBuilder for MazeBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let cells_wide = (self.map.width - 1) / 2;
+        let cells_high = (self.map.height - 1) / 2;
+        let mut visited = vec![false; (cells_wide * cells_high) as usize];
+        let mut stack: Vec<(i32, i32)> = Vec::new();
+
+        let mut dug = 0;
+        let mut current = (0, 0);
+        visited[0] = true;
+        let start_idx = self.cell_tile(0, 0);
+        self.map.tiles[start_idx] = TileType::Floor;
+
+        loop {
+            let (cx, cy) = current;
+            let unvisited: Vec<(i32, i32)> =
+                [(cx - 1, cy), (cx + 1, cy), (cx, cy - 1), (cx, cy + 1)]
+                    .iter()
+                    .filter(|(nx, ny)| {
+                        *nx >= 0
+                            && *ny >= 0
+                            && *nx < cells_wide
+                            && *ny < cells_high
+                            && !visited[(ny * cells_wide + nx) as usize]
+                    })
+                    .copied()
+                    .collect();
+
+            if unvisited.is_empty() {
+                match stack.pop() {
+                    Some(previous) => current = previous,
+                    None => break,
+                }
+                continue;
+            }
+
+            let next = unvisited[(rng.roll_dice(1, unvisited.len() as i32) - 1) as usize];
+            visited[(next.1 * cells_wide + next.0) as usize] = true;
+
+            // Knock down the wall between the two cells and open the new one
+            let wall_idx = self.map.xy_idx(cx + next.0 + 1, cy + next.1 + 1);
+            self.map.tiles[wall_idx] = TileType::Floor;
+            let next_idx = self.cell_tile(next.0, next.1);
+            self.map.tiles[next_idx] = TileType::Floor;
+
+            stack.push(current);
+            current = next;
+
+            dug += 1;
+            if dug % SNAPSHOT_EVERY == 0 {
+                self.take_snapshot();
+            }
+        }
+
+        self.starting_position = Position { x: 1, y: 1 };
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+        self.take_snapshot();
+
+        self.noise_areas = generate_voronoi_spawn_regions(&self.map, rng);
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        let start_idx = self
+            .map
+            .xy_idx(self.starting_position.x, self.starting_position.y);
+        spawn_regions(ecs, &self.noise_areas, start_idx, self.map.depth);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.clone()
+    }
+
+    fn take_snapshot(&mut self) {
+        self.history.push(snapshot(&self.map));
+    }
+}
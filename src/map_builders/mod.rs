@@ -0,0 +1,105 @@
+use super::args::arg_value;
+use super::{Map, Position};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+mod bsp_dungeon;
+mod cellular_automata;
+mod common;
+mod drunkard;
+mod maze;
+mod prefab_builder;
+pub mod prefabs;
+mod simple_map;
+
+use bsp_dungeon::BspDungeonBuilder;
+use cellular_automata::CellularAutomataBuilder;
+use drunkard::DrunkardsWalkBuilder;
+use maze::MazeBuilder;
+use prefab_builder::random_section;
+pub use prefab_builder::PrefabBuilder;
+use simple_map::SimpleMapBuilder;
+
+/// Generates one level: carves out its map, then places whatever lives on it.
+pub trait MapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator);
+    /// Fills the level with monsters, items and traps; the map must already be in `ecs`.
+    fn spawn_entities(&mut self, ecs: &mut World);
+    fn get_map(&self) -> Map;
+    fn get_starting_position(&self) -> Position;
+    /// The map as it looked at each step of `build_map`, ending with the finished level.
+    fn get_snapshot_history(&self) -> Vec<Map>;
+    fn take_snapshot(&mut self);
+}
+
+/// Every builder that can be asked for by name. All but the premade crypt generate a fresh layout.
+pub const BUILDER_NAMES: [&str; 6] = ["rooms", "bsp", "cellular", "drunkard", "maze", "crypt"];
+/// How many of `BUILDER_NAMES` generate their levels, for picking one at random.
+const ALGORITHM_BUILDERS: usize = 5;
+
+/// Percentage chance a random level is the premade crypt.
+const PREMADE_LEVEL_CHANCE: i32 = 5;
+/// Percentage chance a random level gets a vault stamped onto it.
+const SECTION_CHANCE: i32 = 25;
+
+/// Size of the levels the game generates; bigger than the screen, which scrolls to follow the
+/// player.
+pub const LEVEL_WIDTH: i32 = 96;
+pub const LEVEL_HEIGHT: i32 = 64;
+
+/// The builder called `name`, for a `width` by `height` level `depth`.
+pub fn builder_by_name(
+    name: &str,
+    depth: i32,
+    width: i32,
+    height: i32,
+) -> Option<Box<dyn MapBuilder>> {
+    match name {
+        "rooms" => Some(Box::new(SimpleMapBuilder::new(depth, width, height))),
+        "bsp" => Some(Box::new(BspDungeonBuilder::new(depth, width, height))),
+        "cellular" => Some(Box::new(CellularAutomataBuilder::new(depth, width, height))),
+        "drunkard" => Some(Box::new(DrunkardsWalkBuilder::new(depth, width, height))),
+        "maze" => Some(Box::new(MazeBuilder::new(depth, width, height))),
+        "crypt" => Some(Box::new(PrefabBuilder::rex_level(
+            depth,
+            prefabs::CRYPT_LEVEL,
+        ))),
+        _ => None,
+    }
+}
+
+/// Any one of the builders, picked with the run's generator so a seed always gets the same levels.
+/// Generated levels may have a vault stamped onto them and are decorated with prefab rooms.
+pub fn random_builder(
+    depth: i32,
+    width: i32,
+    height: i32,
+    rng: &mut RandomNumberGenerator,
+) -> Box<dyn MapBuilder> {
+    if rng.roll_dice(1, 100) <= PREMADE_LEVEL_CHANCE {
+        return Box::new(PrefabBuilder::rex_level(depth, prefabs::CRYPT_LEVEL));
+    }
+
+    let choice = rng.roll_dice(1, ALGORITHM_BUILDERS as i32) - 1;
+    let mut builder =
+        builder_by_name(BUILDER_NAMES[choice as usize], depth, width, height).unwrap();
+    if rng.roll_dice(1, 100) <= SECTION_CHANCE {
+        if let Some(section) = random_section(depth, rng) {
+            builder = Box::new(PrefabBuilder::sectional(depth, section, builder));
+        }
+    }
+    Box::new(PrefabBuilder::room_vaults(depth, builder))
+}
+
+/// Reads a builder name from a `--builder <name>` command-line argument, if one was given. A name
+/// that isn't in `BUILDER_NAMES` is an error listing the ones that are.
+pub fn builder_from_args() -> Result<Option<String>, String> {
+    match arg_value("--builder") {
+        Some(name) if !BUILDER_NAMES.contains(&name.as_str()) => Err(format!(
+            "Unknown map builder '{}'; choose one of: {}",
+            name,
+            BUILDER_NAMES.join(", ")
+        )),
+        name => Ok(name),
+    }
+}
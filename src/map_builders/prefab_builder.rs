@@ -0,0 +1,331 @@
+use super::common::{place_stairs_furthest, snapshot};
+use super::prefabs::{
+    template_rows, HorizontalPlacement, PrefabSection, VerticalPlacement, SECTIONS, VAULTS,
+};
+use super::MapBuilder;
+use crate::raws::spawn_named_entity;
+use crate::{Map, Position, TileType};
+use rltk::rex::XpFile;
+use rltk::{console, RandomNumberGenerator};
+use specs::prelude::*;
+
+/// The most room decorations a level gets.
+const MAX_VAULTS: i32 = 3;
+
+pub enum PrefabMode {
+    /// A whole premade level, drawn in REX Paint.
+    RexLevel { template: &'static [u8] },
+    /// Stamps a walled vault onto the level made by the previous builder.
+    Sectional { section: &'static PrefabSection },
+    /// Scatters small decorations over the open floor of the level made by the previous builder.
+    RoomVaults,
+}
+
+/// Builds levels from hand-drawn templates, either on their own or on top of another builder.
+pub struct PrefabBuilder {
+    map: Map,
+    starting_position: Position,
+    history: Vec<Map>,
+    mode: PrefabMode,
+    previous_builder: Option<Box<dyn MapBuilder>>,
+    /// Tiles and the names of what the templates spawn on them.
+    spawn_list: Vec<(usize, String)>,
+}
+
+/// What a template character stands for: the tile it becomes, and anything spawned on it.
+fn char_to_tile(ch: char) -> (TileType, Option<&'static str>) {
+    match ch {
+        ' ' | '.' | '@' => (TileType::Floor, None),
+        '#' => (TileType::Wall, None),
+        '>' => (TileType::DownStairs, None),
+        'g' => (TileType::Floor, Some("Goblin")),
+        'o' => (TileType::Floor, Some("Orc")),
+        'E' => (TileType::Floor, Some("Fire Elemental")),
+        '!' => (TileType::Floor, Some("Health Potion")),
+        '?' => (TileType::Floor, Some("Magic Missile Scroll")),
+        '%' => (TileType::Floor, Some("Ration")),
+        '^' => (TileType::Floor, Some("Confusion Gas Trap")),
+        _ => {
+            console::log(format!("Unknown prefab glyph '{}'", ch));
+            (TileType::Floor, None)
+        }
+    }
+}
+
+impl PrefabBuilder {
+    pub fn rex_level(depth: i32, template: &'static [u8]) -> PrefabBuilder {
+        PrefabBuilder::new(depth, PrefabMode::RexLevel { template }, None)
+    }
+
+    pub fn sectional(
+        depth: i32,
+        section: &'static PrefabSection,
+        previous_builder: Box<dyn MapBuilder>,
+    ) -> PrefabBuilder {
+        PrefabBuilder::new(
+            depth,
+            PrefabMode::Sectional { section },
+            Some(previous_builder),
+        )
+    }
+
+    pub fn room_vaults(depth: i32, previous_builder: Box<dyn MapBuilder>) -> PrefabBuilder {
+        PrefabBuilder::new(depth, PrefabMode::RoomVaults, Some(previous_builder))
+    }
+
+    fn new(
+        depth: i32,
+        mode: PrefabMode,
+        previous_builder: Option<Box<dyn MapBuilder>>,
+    ) -> PrefabBuilder {
+        PrefabBuilder {
+            map: Map::new(depth, 1, 1),
+            starting_position: Position { x: 0, y: 0 },
+            history: Vec::new(),
+            mode,
+            previous_builder,
+            spawn_list: Vec::new(),
+        }
+    }
+
+    /// Sets the tile at `(x, y)` from a template character, queueing whatever it spawns.
+    fn apply_char(&mut self, ch: char, x: i32, y: i32) {
+        let idx = self.map.xy_idx(x, y);
+        let (tile, spawn) = char_to_tile(ch);
+        self.map.tiles[idx] = tile;
+        if let Some(name) = spawn {
+            self.spawn_list.push((idx, name.to_string()));
+        }
+        if ch == '@' {
+            self.starting_position = Position { x, y };
+        }
+    }
+
+    /// Builds the level underneath with the previous builder and takes it over.
+    fn build_previous(&mut self, rng: &mut RandomNumberGenerator) {
+        if let Some(previous) = self.previous_builder.as_mut() {
+            previous.build_map(rng);
+            self.map = previous.get_map();
+            self.starting_position = previous.get_starting_position();
+            self.history = previous.get_snapshot_history();
+        }
+    }
+
+    fn load_rex_level(&mut self, template: &'static [u8]) {
+        let xp = XpFile::read(&mut &template[..]).expect("Unable to read prefab level");
+        let layer = &xp.layers[0];
+        self.map = Map::new(self.map.depth, layer.width as i32, layer.height as i32);
+
+        for y in 0..layer.height {
+            for x in 0..layer.width {
+                if let Some(cell) = layer.get(x, y) {
+                    let ch = rltk::to_char(cell.ch as u8);
+                    self.apply_char(ch, x as i32, y as i32);
+                }
+            }
+        }
+        self.take_snapshot();
+    }
+
+    fn apply_section(&mut self, rng: &mut RandomNumberGenerator, section: &PrefabSection) {
+        self.build_previous(rng);
+
+        let rows = template_rows(section.template);
+        let height = rows.len() as i32;
+        let width = rows[0].len() as i32;
+        let x0 = match section.placement.0 {
+            HorizontalPlacement::Left => 1,
+            HorizontalPlacement::Center => (self.map.width - width) / 2,
+            HorizontalPlacement::Right => self.map.width - width - 1,
+        };
+        let y0 = match section.placement.1 {
+            VerticalPlacement::Top => 1,
+            VerticalPlacement::Center => (self.map.height - height) / 2,
+            VerticalPlacement::Bottom => self.map.height - height - 1,
+        };
+
+        // Leave the level as it is rather than bury the player
+        let start = &self.starting_position;
+        let covers_start = start.x >= x0 - 1
+            && start.x <= x0 + width
+            && start.y >= y0 - 1
+            && start.y <= y0 + height;
+        if x0 < 1 || y0 < 1 || covers_start {
+            return;
+        }
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, ch) in row.iter().enumerate() {
+                self.apply_char(*ch, x0 + x as i32, y0 + y as i32);
+            }
+        }
+        self.take_snapshot();
+
+        // Dig out from each gap in the outer wall until the tunnel meets the level
+        for (y, row) in rows.iter().enumerate() {
+            for (x, ch) in row.iter().enumerate() {
+                if char_to_tile(*ch).0 == TileType::Wall {
+                    continue;
+                }
+                let (x, y) = (x as i32, y as i32);
+                let (dx, dy) = if x == 0 {
+                    (-1, 0)
+                } else if x == width - 1 {
+                    (1, 0)
+                } else if y == 0 {
+                    (0, -1)
+                } else if y == height - 1 {
+                    (0, 1)
+                } else {
+                    continue;
+                };
+                let mut tx = x0 + x + dx;
+                let mut ty = y0 + y + dy;
+                while tx > 0 && ty > 0 && tx < self.map.width - 1 && ty < self.map.height - 1 {
+                    let idx = self.map.xy_idx(tx, ty);
+                    if self.map.tiles[idx] != TileType::Wall {
+                        break;
+                    }
+                    self.map.tiles[idx] = TileType::Floor;
+                    tx += dx;
+                    ty += dy;
+                }
+            }
+        }
+
+        // The vault may have cut the old stairs off, or covered them
+        let start_idx = self
+            .map
+            .xy_idx(self.starting_position.x, self.starting_position.y);
+        place_stairs_furthest(&mut self.map, start_idx);
+        self.take_snapshot();
+    }
+
+    fn apply_room_vaults(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build_previous(rng);
+
+        let depth = self.map.depth;
+        let start_idx = self
+            .map
+            .xy_idx(self.starting_position.x, self.starting_position.y);
+        // Vaults never overlap each other, the player or the stairs
+        let mut used_tiles: Vec<usize> = vec![start_idx];
+        used_tiles.extend(
+            self.map
+                .tiles
+                .iter()
+                .enumerate()
+                .filter(|(_, tile)| **tile == TileType::DownStairs)
+                .map(|(idx, _)| idx),
+        );
+
+        let num_vaults = rng.roll_dice(1, MAX_VAULTS + 1) - 1;
+        for _i in 0..num_vaults {
+            let candidates: Vec<_> = VAULTS
+                .iter()
+                .filter(|vault| depth >= vault.min_depth && depth <= vault.max_depth)
+                .collect();
+            if candidates.is_empty() {
+                return;
+            }
+            let vault = candidates[(rng.roll_dice(1, candidates.len() as i32) - 1) as usize];
+            let rows = template_rows(vault.template);
+            let height = rows.len() as i32;
+            let width = rows[0].len() as i32;
+
+            // Anywhere the vault sits wholly on unused floor will do
+            let mut spots: Vec<(i32, i32)> = Vec::new();
+            for y in 1..self.map.height - height {
+                for x in 1..self.map.width - width {
+                    let fits = (0..height).all(|vy| {
+                        (0..width).all(|vx| {
+                            let idx = self.map.xy_idx(x + vx, y + vy);
+                            self.map.tiles[idx] == TileType::Floor && !used_tiles.contains(&idx)
+                        })
+                    });
+                    if fits {
+                        spots.push((x, y));
+                    }
+                }
+            }
+            if spots.is_empty() {
+                continue;
+            }
+
+            let (x0, y0) = spots[(rng.roll_dice(1, spots.len() as i32) - 1) as usize];
+            for (y, row) in rows.iter().enumerate() {
+                for (x, ch) in row.iter().enumerate() {
+                    let (x, y) = (x0 + x as i32, y0 + y as i32);
+                    self.apply_char(*ch, x, y);
+                    used_tiles.push(self.map.xy_idx(x, y));
+                }
+            }
+            self.take_snapshot();
+        }
+    }
+}
+
+impl MapBuilder for PrefabBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        match self.mode {
+            PrefabMode::RexLevel { template } => self.load_rex_level(template),
+            PrefabMode::Sectional { section } => self.apply_section(rng, section),
+            PrefabMode::RoomVaults => self.apply_room_vaults(rng),
+        }
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        // The templates' own spawns go first, and `spawn_region` keeps the level's random ones
+        // off every tile that already holds something. A vault stamped over a section's spawn
+        // wins the tile.
+        let mut filled: Vec<(i32, i32)> = ecs
+            .read_storage::<Position>()
+            .join()
+            .map(|pos| (pos.x, pos.y))
+            .collect();
+        for (idx, name) in self.spawn_list.iter() {
+            let x = *idx as i32 % self.map.width;
+            let y = *idx as i32 / self.map.width;
+            if self.map.tiles[*idx] == TileType::Wall || filled.contains(&(x, y)) {
+                continue;
+            }
+            spawn_named_entity(ecs, name, x, y);
+            filled.push((x, y));
+        }
+
+        if let Some(previous) = self.previous_builder.as_mut() {
+            previous.spawn_entities(ecs);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.clone()
+    }
+
+    fn take_snapshot(&mut self) {
+        self.history.push(snapshot(&self.map));
+    }
+}
+
+/// A section that can be stamped on level `depth`, if any.
+pub fn random_section(
+    depth: i32,
+    rng: &mut RandomNumberGenerator,
+) -> Option<&'static PrefabSection> {
+    let candidates: Vec<&'static PrefabSection> = SECTIONS
+        .iter()
+        .filter(|section| depth >= section.min_depth)
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(candidates[(rng.roll_dice(1, candidates.len() as i32) - 1) as usize])
+}
@@ -0,0 +1,123 @@
+/// The premade crypt level, drawn in REX Paint.
+pub const CRYPT_LEVEL: &[u8] = include_bytes!("../../raws/prefabs/crypt.xp");
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum HorizontalPlacement {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum VerticalPlacement {
+    Top,
+    Center,
+    Bottom,
+}
+
+/// A walled-off vault stamped over part of a generated level. Gaps in its outer wall are dug
+/// out until they meet the level's floor.
+pub struct PrefabSection {
+    pub template: &'static str,
+    pub placement: (HorizontalPlacement, VerticalPlacement),
+    pub min_depth: i32,
+}
+
+/// A small decoration dropped onto open floor; its outer ring should stay floor so it never
+/// blocks a way through.
+pub struct PrefabVault {
+    pub template: &'static str,
+    pub min_depth: i32,
+    pub max_depth: i32,
+}
+
+pub static SECTIONS: [PrefabSection; 3] = [
+    PrefabSection {
+        template: "
+###########
+#.........#
+#.o.o.o.o.#
+#.........#
+....!.%...#
+#.........#
+###########
+",
+        placement: (HorizontalPlacement::Right, VerticalPlacement::Center),
+        min_depth: 2,
+    },
+    PrefabSection {
+        template: "
+#########
+#.......#
+#..?.?..#
+#..g.g..#
+#.......#
+####.####
+",
+        placement: (HorizontalPlacement::Center, VerticalPlacement::Top),
+        min_depth: 1,
+    },
+    PrefabSection {
+        template: "
+#######.#####
+#...........#
+#.^.......^.#
+#.....E......
+#.^.......^.#
+#.....!.....#
+#############
+",
+        placement: (HorizontalPlacement::Left, VerticalPlacement::Bottom),
+        min_depth: 4,
+    },
+];
+
+pub static VAULTS: [PrefabVault; 3] = [
+    // Goblin camp around a ration
+    PrefabVault {
+        template: "
+.....
+.g.g.
+..%..
+.g.g.
+.....
+",
+        min_depth: 1,
+        max_depth: 4,
+    },
+    // Treasure closet
+    PrefabVault {
+        template: "
+.....
+.###.
+.#!..
+.###.
+.....
+",
+        min_depth: 2,
+        max_depth: 100,
+    },
+    // Scrolls ringed by traps
+    PrefabVault {
+        template: "
+.......
+.^.^.^.
+.^?!?^.
+.^.^.^.
+.......
+",
+        min_depth: 3,
+        max_depth: 100,
+    },
+];
+
+/// A template's rows as characters, skipping the blank lines around it. Templates are plain text,
+/// one row per line: `#` is wall, `.` or a space is floor, and letters and symbols place spawns
+/// on floor (see `prefab_builder::char_to_tile`).
+pub fn template_rows(template: &str) -> Vec<Vec<char>> {
+    template
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().collect())
+        .collect()
+}
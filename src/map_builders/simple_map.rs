@@ -0,0 +1,102 @@
+use super::common::{
+    apply_horizontal_tunnel, apply_room_to_map, apply_vertical_tunnel, snapshot, spawn_doors,
+};
+use super::MapBuilder;
+use crate::{spawner, Map, Position, Rect, TileType};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+const MAX_ROOMS: i32 = 30;
+const MIN_SIZE: i32 = 6;
+const MAX_SIZE: i32 = 10;
+
+/// Makes a new map using the algorithm from http://rogueliketutorials.com/tutorials/tcod/part-3/
+/// This gives a handful of random rooms and corridors joining them together.
+/// The stairs down are placed in the center of the last room.
+pub struct SimpleMapBuilder {
+    map: Map,
+    starting_position: Position,
+    history: Vec<Map>,
+    rooms: Vec<Rect>,
+}
+
+impl SimpleMapBuilder {
+    pub fn new(depth: i32, width: i32, height: i32) -> SimpleMapBuilder {
+        SimpleMapBuilder {
+            map: Map::new(depth, width, height),
+            starting_position: Position { x: 0, y: 0 },
+            history: Vec::new(),
+            rooms: Vec::new(),
+        }
+    }
+}
+
+impl MapBuilder for SimpleMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        for _i in 0..MAX_ROOMS {
+            let w = rng.range(MIN_SIZE, MAX_SIZE);
+            let h = rng.range(MIN_SIZE, MAX_SIZE);
+            let x = rng.roll_dice(1, self.map.width - w - 1) - 1;
+            let y = rng.roll_dice(1, self.map.height - h - 1) - 1;
+            let new_room = Rect::new(x, y, w, h);
+            let mut ok = true;
+            for other_room in self.rooms.iter() {
+                if new_room.intersect(other_room) {
+                    ok = false
+                }
+            }
+            if ok {
+                apply_room_to_map(&mut self.map, &new_room);
+
+                if !self.rooms.is_empty() {
+                    let (new_x, new_y) = new_room.center();
+                    let (prev_x, prev_y) = self.rooms[self.rooms.len() - 1].center();
+                    if rng.range(0, 2) == 1 {
+                        apply_horizontal_tunnel(&mut self.map, prev_x, new_x, prev_y);
+                        apply_vertical_tunnel(&mut self.map, prev_y, new_y, new_x);
+                    } else {
+                        apply_vertical_tunnel(&mut self.map, prev_y, new_y, prev_x);
+                        apply_horizontal_tunnel(&mut self.map, prev_x, new_x, new_y);
+                    }
+                }
+
+                self.rooms.push(new_room);
+                self.take_snapshot();
+            }
+        }
+
+        let (stairs_x, stairs_y) = self.rooms[self.rooms.len() - 1].center();
+        let stairs_idx = self.map.xy_idx(stairs_x, stairs_y);
+        self.map.tiles[stairs_idx] = TileType::DownStairs;
+        self.take_snapshot();
+
+        let (start_x, start_y) = self.rooms[0].center();
+        self.starting_position = Position {
+            x: start_x,
+            y: start_y,
+        };
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        spawn_doors(ecs, &self.rooms);
+        for room in self.rooms.iter().skip(1) {
+            spawner::spawn_room(ecs, room, self.map.depth);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<Map> {
+        self.history.clone()
+    }
+
+    fn take_snapshot(&mut self) {
+        self.history.push(snapshot(&self.map));
+    }
+}
@@ -1,4 +1,4 @@
-use super::{BlocksTile, Map, Position};
+use super::{BlocksTile, Door, Map, Position};
 use specs::prelude::*;
 
 pub struct MapIndexingSystem {}
@@ -8,14 +8,16 @@ impl<'a> System<'a> for MapIndexingSystem {
         WriteExpect<'a, Map>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, BlocksTile>,
+        ReadStorage<'a, Door>,
         Entities<'a>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, position, blockers, entities) = data;
+        let (mut map, position, blockers, doors, entities) = data;
 
         map.populate_blocked();
         map.clear_content_index();
+        map.closed_doors.clear();
 
         for (entity, position) in (&entities, &position).join() {
             let idx = map.xy_idx(position.x, position.y);
@@ -25,6 +27,13 @@ impl<'a> System<'a> for MapIndexingSystem {
                 map.blocked[idx] = true;
             }
 
+            if let Some(door) = doors.get(entity) {
+                if !door.open {
+                    map.blocked[idx] = true;
+                    map.closed_doors.insert(idx);
+                }
+            }
+
             map.tile_content[idx].push(entity);
         }
     }
@@ -1,39 +1,153 @@
-use super::{gamelog::GameLog, CombatStats, Name, SufferDamage, WantsToMelee};
-use rltk::console;
+use super::turn_system::ATTACK_COST;
+use super::{
+    gamelog::GameLog, CombatStats, DefenseBonus, Energy, EquipmentSlot, Equipped, Hit,
+    MeleePowerBonus, MeleeWeapon, Name, SufferDamage, WantsToMelee,
+};
+use rltk::{DiceType, RandomNumberGenerator};
 use specs::prelude::*;
 
+/// Every attack is a d20 roll; a defender's armour class is this plus their defense.
+pub const BASE_ARMOUR_CLASS: i32 = 10;
+
+/// What became of a d20 attack roll.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum AttackRoll {
+    /// A natural 1 misses no matter how skilled the attacker is.
+    Fumble,
+    Miss,
+    Hit,
+    /// A natural 20 always hits and rolls the damage dice twice.
+    Critical,
+}
+
+/// Resolves a `natural` d20 roll plus `attack_bonus` against the defender's `armour_class`.
+pub fn attack_roll(natural: i32, attack_bonus: i32, armour_class: i32) -> AttackRoll {
+    match natural {
+        1 => AttackRoll::Fumble,
+        20 => AttackRoll::Critical,
+        _ if natural + attack_bonus >= armour_class => AttackRoll::Hit,
+        _ => AttackRoll::Miss,
+    }
+}
+
 pub struct MeleeCombatSystem {}
 impl<'a> System<'a> for MeleeCombatSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = (
         Entities<'a>,
+        ReadExpect<'a, Entity>,
         WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RandomNumberGenerator>,
         WriteStorage<'a, WantsToMelee>,
         ReadStorage<'a, Name>,
         ReadStorage<'a, CombatStats>,
         WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, MeleePowerBonus>,
+        ReadStorage<'a, MeleeWeapon>,
+        ReadStorage<'a, DefenseBonus>,
+        ReadStorage<'a, Equipped>,
+        WriteStorage<'a, Energy>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut log, mut wants_melee, names, combat_stats, mut inflict_damage) = data;
+        let (
+            entities,
+            player_entity,
+            mut log,
+            mut rng,
+            mut wants_melee,
+            names,
+            combat_stats,
+            mut inflict_damage,
+            melee_power_bonuses,
+            melee_weapons,
+            defense_bonuses,
+            equipped,
+            mut energy,
+        ) = data;
 
-        for (_entity, wants_melee, name, stats) in
+        for (entity, wants_melee, name, stats) in
             (&entities, &wants_melee, &names, &combat_stats).join()
         {
+            Energy::spend(&mut energy, entity, ATTACK_COST);
             if stats.hp > 0 {
                 let target_stats = combat_stats.get(wants_melee.target).unwrap();
                 if target_stats.hp > 0 {
                     let target_name = names.get(wants_melee.target).unwrap();
-                    let damage = i32::max(0, stats.power - target_stats.defense);
-                    if damage == 0 {
-                        log.entries.push(format!(
-                            "{} is unable to hurt {}",
-                            &name.name, &target_name.name
-                        ));
-                    } else {
-                        log.entries.push(format!(
-                            "{} hits {}, for {} hp.",
-                            &name.name, &target_name.name, damage
-                        ));
-                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+                    // Gear only counts while it is equipped by the combatant
+                    let offensive_bonus: i32 = (&melee_power_bonuses, &equipped)
+                        .join()
+                        .filter(|(_, equipped_by)| equipped_by.owner == entity)
+                        .map(|(bonus, _)| bonus.power)
+                        .sum();
+                    let defensive_bonus: i32 = (&defense_bonuses, &equipped)
+                        .join()
+                        .filter(|(_, equipped_by)| equipped_by.owner == wants_melee.target)
+                        .map(|(bonus, _)| bonus.defense)
+                        .sum();
+                    // A wielded weapon replaces the attacker's own fists, claws or teeth
+                    let (damage_dice, damage_type) = (&melee_weapons, &equipped)
+                        .join()
+                        .find(|(_, equipped_by)| {
+                            equipped_by.owner == entity && equipped_by.slot == EquipmentSlot::Weapon
+                        })
+                        .map(|(weapon, _)| (weapon.damage, weapon.damage_type))
+                        .or_else(|| {
+                            melee_weapons
+                                .get(entity)
+                                .map(|weapon| (weapon.damage, weapon.damage_type))
+                        })
+                        .unwrap_or_default();
+
+                    let natural = rng.roll_dice(1, 20);
+                    let roll = attack_roll(
+                        natural,
+                        stats.power + offensive_bonus,
+                        BASE_ARMOUR_CLASS + target_stats.defense + defensive_bonus,
+                    );
+                    match roll {
+                        AttackRoll::Fumble => {
+                            log.entries.push(format!(
+                                "{} fumbles and misses {}.",
+                                &name.name, &target_name.name
+                            ));
+                        }
+                        AttackRoll::Miss => {
+                            log.entries
+                                .push(format!("{} misses {}.", &name.name, &target_name.name));
+                        }
+                        AttackRoll::Hit | AttackRoll::Critical => {
+                            let dice = if roll == AttackRoll::Critical {
+                                DiceType::new(
+                                    damage_dice.n_dice * 2,
+                                    damage_dice.die_type,
+                                    damage_dice.bonus,
+                                )
+                            } else {
+                                damage_dice
+                            };
+                            let damage = i32::max(1, rng.roll(dice));
+                            if roll == AttackRoll::Critical {
+                                log.entries.push(format!(
+                                    "{} critically hits {}, for {} hp!",
+                                    &name.name, &target_name.name, damage
+                                ));
+                            } else {
+                                log.entries.push(format!(
+                                    "{} hits {}, for {} hp.",
+                                    &name.name, &target_name.name, damage
+                                ));
+                            }
+                            SufferDamage::new_damage(
+                                &mut inflict_damage,
+                                wants_melee.target,
+                                Hit {
+                                    amount: damage,
+                                    damage_type,
+                                    from_player: entity == *player_entity,
+                                    source: name.name.clone(),
+                                },
+                            );
+                        }
                     }
                 }
             }
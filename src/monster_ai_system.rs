@@ -1,9 +1,51 @@
-use crate::components::Confusion;
-
-use super::{Map, Monster, Name, Position, RunState, Viewshed, WantsToMelee};
-use rltk::{console, Point};
+use super::status_effect_system::has_status;
+use super::turn_system::MOVE_COST;
+use super::{
+    gamelog::GameLog, Ai, AiState, CombatStats, Door, Energy, EntityMoved, Map, Monster, MyTurn,
+    Name, Position, Renderable, StatusEffect, StatusKind, Viewshed, WantsToMelee,
+};
+use rltk::{BaseMap, Point, RandomNumberGenerator};
 use specs::prelude::*;
 
+/// A sleeping monster within this distance hears the player and wakes.
+const HEARING_RANGE: f32 = 2.5;
+/// A sleeping monster that can see the player notices them on a 1 in this many roll each turn.
+const NOTICE_CHANCE: i32 = 4;
+/// Monsters flee once their hit points fall below this percentage of their maximum.
+const FLEE_PERCENT: i32 = 25;
+/// Turns spent searching around the player's last known position before giving up.
+pub const SEARCH_TURNS: i32 = 5;
+
+/// Moves the monster at `pos` one step to the tile `idx`, keeping the map's blocking up to date.
+/// A closed door on `idx` is opened instead, which uses up the step; returns whether it moved.
+fn step_to(
+    map: &mut Map,
+    pos: &mut Position,
+    viewshed: &mut Viewshed,
+    idx: usize,
+    doors: &mut WriteStorage<Door>,
+    renderables: &mut WriteStorage<Renderable>,
+) -> bool {
+    if map.closed_doors.contains(&idx) {
+        for entity in map.tile_content[idx].clone() {
+            if let (Some(door), Some(renderable)) =
+                (doors.get_mut(entity), renderables.get_mut(entity))
+            {
+                door.set_open(true, renderable, map, idx);
+            }
+        }
+        return false;
+    }
+
+    let old_idx = map.xy_idx(pos.x, pos.y);
+    map.blocked[old_idx] = false;
+    pos.x = idx as i32 % map.width;
+    pos.y = idx as i32 / map.width;
+    map.blocked[idx] = true;
+    viewshed.dirty = true;
+    true
+}
+
 pub struct MonsterAI {}
 
 impl<'a> System<'a> for MonsterAI {
@@ -12,13 +54,22 @@ impl<'a> System<'a> for MonsterAI {
         WriteExpect<'a, Map>,
         ReadExpect<'a, Point>,
         ReadExpect<'a, Entity>,
-        ReadExpect<'a, RunState>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, GameLog>,
         Entities<'a>,
         WriteStorage<'a, Viewshed>,
         ReadStorage<'a, Monster>,
+        WriteStorage<'a, Ai>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Name>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, WantsToMelee>,
-        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, StatusEffect>,
+        WriteStorage<'a, MyTurn>,
+        WriteStorage<'a, Energy>,
+        WriteStorage<'a, EntityMoved>,
+        WriteStorage<'a, Door>,
+        WriteStorage<'a, Renderable>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -26,63 +77,181 @@ impl<'a> System<'a> for MonsterAI {
             mut map,
             player_pos,
             player_entity,
-            runstate,
+            mut rng,
+            mut log,
             entities,
             mut viewshed,
             monster,
+            mut ai,
+            combat_stats,
+            names,
             mut pos,
             mut wants_to_melee,
-            mut confused,
+            statuses,
+            mut turns,
+            mut energy,
+            mut entity_moved,
+            mut doors,
+            mut renderables,
         ) = data;
 
-        if *runstate != RunState::MonsterTurn {
-            return;
-        }
-
-        for (entity, mut viewshed, _monster, mut pos) in
-            (&entities, &mut viewshed, &monster, &mut pos).join()
+        // Only monsters the turn system has given a turn may act
+        for (entity, viewshed, _monster, ai, stats, pos, _turn) in (
+            &entities,
+            &mut viewshed,
+            &monster,
+            &mut ai,
+            &combat_stats,
+            &mut pos,
+            &turns,
+        )
+            .join()
         {
-            let mut can_act = true;
+            // Every action below takes a move's worth of energy, except attacks, which the
+            // melee system charges for
+            let mut attack = false;
+            let distance =
+                rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
+            let can_see_player = viewshed.visible_tiles.contains(&*player_pos);
+
+            // Confused monsters lose their turn
+            if has_status(&statuses, entity, StatusKind::Confusion) {
+                Energy::spend(&mut energy, entity, MOVE_COST);
+                continue;
+            }
 
-            let is_confused = confused.get_mut(entity);
-            if let Some(i_am_confused) = is_confused {
-                i_am_confused.turns -= 1;
-                if i_am_confused.turns < 1 {
-                    confused.remove(entity);
+            if ai.state == AiState::Sleeping {
+                let heard = distance < HEARING_RANGE;
+                let hurt = stats.hp < stats.max_hp;
+                let noticed = can_see_player && rng.roll_dice(1, NOTICE_CHANCE) == 1;
+                if heard || hurt || noticed {
+                    ai.state = AiState::Chasing {
+                        x: player_pos.x,
+                        y: player_pos.y,
+                    };
+                    if map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+                        if let Some(name) = names.get(entity) {
+                            log.entries.push(format!("{} wakes up.", name.name));
+                        }
+                    }
                 }
-                can_act = false;
+                // Waking up takes the whole turn
+                Energy::spend(&mut energy, entity, MOVE_COST);
+                continue;
             }
 
-            if can_act {
-                let distance =
-                    rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
-                if distance < 1.5 {
-                    wants_to_melee
-                        .insert(
-                            entity,
-                            WantsToMelee {
-                                target: *player_entity,
-                            },
-                        )
-                        .expect("Unable to insert attack");
-                } else if viewshed.visible_tiles.contains(&*player_pos) {
-                    let path = rltk::a_star_search(
-                        map.xy_idx(pos.x, pos.y),
-                        map.xy_idx(player_pos.x, player_pos.y),
-                        &mut *map,
-                    );
+            if stats.hp * 100 < stats.max_hp * FLEE_PERCENT {
+                ai.state = AiState::Fleeing;
+            } else if can_see_player {
+                ai.state = AiState::Chasing {
+                    x: player_pos.x,
+                    y: player_pos.y,
+                };
+            }
 
-                    if path.success && path.steps.len() > 1 {
-                        let mut idx = map.xy_idx(pos.x, pos.y);
-                        map.blocked[idx] = false;
-                        pos.x = path.steps[1] as i32 % map.width;
-                        pos.y = path.steps[1] as i32 / map.width;
-                        idx = map.xy_idx(pos.x, pos.y);
-                        map.blocked[idx] = true;
-                        viewshed.dirty = true;
+            match ai.state {
+                AiState::Sleeping => {}
+                AiState::Fleeing => {
+                    // Take whichever free neighbouring tile is furthest from the player
+                    let idx = map.xy_idx(pos.x, pos.y);
+                    let w = map.width as usize;
+                    let furthest = map
+                        .get_available_exits(idx)
+                        .iter()
+                        .map(|(exit, _)| {
+                            let to = Point::new(exit % w, exit / w);
+                            (
+                                *exit,
+                                rltk::DistanceAlg::Pythagoras.distance2d(to, *player_pos),
+                            )
+                        })
+                        .filter(|(_, exit_distance)| *exit_distance > distance)
+                        .max_by(|a, b| a.1.total_cmp(&b.1));
+                    if let Some((exit, _)) = furthest {
+                        if step_to(&mut map, pos, viewshed, exit, &mut doors, &mut renderables) {
+                            entity_moved
+                                .insert(entity, EntityMoved {})
+                                .expect("Unable to insert marker");
+                        }
+                    } else if distance < 1.5 {
+                        // Cornered
+                        attack = true;
+                    }
+                }
+                AiState::Chasing { x, y } => {
+                    if distance < 1.5 {
+                        attack = true;
+                    } else {
+                        let path =
+                            rltk::a_star_search(map.xy_idx(pos.x, pos.y), map.xy_idx(x, y), &*map);
+                        if path.success
+                            && path.steps.len() > 1
+                            && step_to(
+                                &mut map,
+                                pos,
+                                viewshed,
+                                path.steps[1],
+                                &mut doors,
+                                &mut renderables,
+                            )
+                        {
+                            entity_moved
+                                .insert(entity, EntityMoved {})
+                                .expect("Unable to insert marker");
+                        }
+                        // Nobody there any more, or no way through
+                        if !path.success || (pos.x == x && pos.y == y) {
+                            ai.state = AiState::Searching {
+                                turns: SEARCH_TURNS,
+                            };
+                        }
+                    }
+                }
+                AiState::Searching { .. } | AiState::Wandering => {
+                    if let AiState::Searching { turns } = ai.state {
+                        ai.state = if turns > 1 {
+                            AiState::Searching { turns: turns - 1 }
+                        } else {
+                            AiState::Wandering
+                        };
+                    }
+
+                    // Searching walks at random like wandering, but from where the player was
+                    // last seen
+                    let idx = map.xy_idx(pos.x, pos.y);
+                    let exits = map.get_available_exits(idx);
+                    if !exits.is_empty() {
+                        let choice = rng.roll_dice(1, exits.len() as i32) - 1;
+                        if step_to(
+                            &mut map,
+                            pos,
+                            viewshed,
+                            exits[choice as usize].0,
+                            &mut doors,
+                            &mut renderables,
+                        ) {
+                            entity_moved
+                                .insert(entity, EntityMoved {})
+                                .expect("Unable to insert marker");
+                        }
                     }
                 }
             }
+
+            if attack {
+                wants_to_melee
+                    .insert(
+                        entity,
+                        WantsToMelee {
+                            target: *player_entity,
+                        },
+                    )
+                    .expect("Unable to insert attack");
+            } else {
+                Energy::spend(&mut energy, entity, MOVE_COST);
+            }
         }
+
+        turns.clear();
     }
 }
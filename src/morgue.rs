@@ -0,0 +1,105 @@
+use super::gamelog::GameLog;
+use super::record_paths::RecordPaths;
+use super::run_stats::RunStats;
+use super::seed::GameSeed;
+use super::{CombatStats, Equipped, Experience, HungerClock, InBackpack, Map, Name};
+use specs::prelude::*;
+use std::fmt::Write;
+
+/// How many of the last log messages a morgue file keeps.
+const MORGUE_LOG_ENTRIES: usize = 20;
+
+/// A plain-text account of the player's run: how far they got, what they killed and carried,
+/// and how it ended.
+pub fn morgue_text(ecs: &World) -> String {
+    let player_entity = *ecs.fetch::<Entity>();
+    let seed = ecs.fetch::<GameSeed>().seed;
+    let depth = ecs.fetch::<Map>().depth;
+    let run_stats = ecs.fetch::<RunStats>();
+    let log = ecs.fetch::<GameLog>();
+    let names = ecs.read_storage::<Name>();
+
+    let mut text = String::new();
+    writeln!(text, "Rusty Roguelike morgue file").unwrap();
+    writeln!(text).unwrap();
+    writeln!(text, "Seed: {}", seed).unwrap();
+    writeln!(text, "Depth reached: {}", depth).unwrap();
+    writeln!(text, "Turns survived: {}", run_stats.turns).unwrap();
+    if let Some(cause) = &run_stats.cause_of_death {
+        writeln!(text, "Killed by: {}", cause).unwrap();
+    }
+
+    writeln!(text).unwrap();
+    writeln!(text, "Final stats:").unwrap();
+    if let Some(xp) = ecs.read_storage::<Experience>().get(player_entity) {
+        writeln!(text, "  Level {} ({} XP)", xp.level, xp.xp).unwrap();
+    }
+    if let Some(stats) = ecs.read_storage::<CombatStats>().get(player_entity) {
+        writeln!(text, "  HP: {} / {}", stats.hp, stats.max_hp).unwrap();
+        writeln!(text, "  Power: {}", stats.power).unwrap();
+        writeln!(text, "  Defense: {}", stats.defense).unwrap();
+    }
+    if let Some(clock) = ecs.read_storage::<HungerClock>().get(player_entity) {
+        writeln!(text, "  Hunger: {:?}", clock.state).unwrap();
+    }
+
+    // Kills are grouped by monster, in the order each kind was first killed
+    let mut kills: Vec<(&str, i32)> = Vec::new();
+    for name in run_stats.kills.iter() {
+        match kills.iter_mut().find(|(n, _)| *n == name.as_str()) {
+            Some((_, count)) => *count += 1,
+            None => kills.push((name, 1)),
+        }
+    }
+    writeln!(text).unwrap();
+    writeln!(text, "Kills ({}):", run_stats.kills.len()).unwrap();
+    for (name, count) in kills.iter() {
+        writeln!(text, "  {} {}", count, name).unwrap();
+    }
+
+    writeln!(text).unwrap();
+    writeln!(text, "Equipped:").unwrap();
+    for (equipped, name) in (&ecs.read_storage::<Equipped>(), &names).join() {
+        if equipped.owner == player_entity {
+            writeln!(text, "  {} ({:?})", name.name, equipped.slot).unwrap();
+        }
+    }
+    writeln!(text).unwrap();
+    writeln!(text, "Backpack:").unwrap();
+    for (pack, name) in (&ecs.read_storage::<InBackpack>(), &names).join() {
+        if pack.owner == player_entity {
+            writeln!(text, "  {}", name.name).unwrap();
+        }
+    }
+
+    writeln!(text).unwrap();
+    writeln!(text, "Last messages:").unwrap();
+    let skip = log.entries.len().saturating_sub(MORGUE_LOG_ENTRIES);
+    for entry in log.entries.iter().skip(skip) {
+        writeln!(text, "  {}", entry).unwrap();
+    }
+
+    text
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write_morgue(_ecs: &World) -> Option<String> {
+    None
+}
+
+/// Writes `morgue_text` to a new file in the morgue directory, named after the seed and the time
+/// of death, and returns its path.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_morgue(ecs: &World) -> Option<String> {
+    let seed = ecs.fetch::<GameSeed>().seed;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let morgue_dir = ecs.fetch::<RecordPaths>().morgue_dir.clone();
+    let path = morgue_dir.join(format!("morgue-{}-{}.txt", seed, timestamp));
+
+    std::fs::create_dir_all(&morgue_dir).ok()?;
+    std::fs::write(&path, morgue_text(ecs)).ok()?;
+    Some(path.display().to_string())
+}
@@ -1,20 +1,62 @@
-use crate::components::{CombatStats, RunState, WantsToMelee, WantsToPickupItem};
+use crate::components::{
+    CombatStats, Door, Energy, EntityMoved, Renderable, RunState, StatusEffect, StatusKind,
+    WantsToMelee, WantsToPickupItem,
+};
 
-use super::{gamelog::GameLog, Item, Map, Player, Position, State, TileType, Viewshed, MAP_WIDTH};
-use rltk::{console, Point, Rltk, VirtualKeyCode};
+use super::gui::LevelUpStat;
+use super::status_effect_system::has_status;
+use super::turn_system::MOVE_COST;
+use super::{gamelog::GameLog, Item, Map, Player, Position, State, TileType, Viewshed};
+use rltk::{Point, RandomNumberGenerator, VirtualKeyCode};
 use specs::prelude::*;
-use std::cmp::{max, min};
 
-pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+/// Where a confused player may stumble instead of the way they meant to go.
+const STUMBLE_DIRECTIONS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Moves, attacks or opens a door in the given direction, returning whether anything happened. A
+/// sober bump into a wall or the map edge does nothing and takes no time.
+pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    let confused = has_status(
+        &ecs.read_storage::<StatusEffect>(),
+        player_entity,
+        StatusKind::Confusion,
+    );
+    let (delta_x, delta_y) = if confused {
+        let roll = ecs
+            .write_resource::<RandomNumberGenerator>()
+            .roll_dice(1, STUMBLE_DIRECTIONS.len() as i32);
+        ecs.write_resource::<GameLog>()
+            .entries
+            .push("You stumble around in confusion!".to_string());
+        STUMBLE_DIRECTIONS[roll as usize - 1]
+    } else {
+        (delta_x, delta_y)
+    };
+
     let mut positions = ecs.write_storage::<Position>();
     let mut players = ecs.write_storage::<Player>();
     let mut viewshed = ecs.write_storage::<Viewshed>();
     let combat_stats = ecs.read_storage::<CombatStats>();
     let entities = ecs.entities();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let mut energy = ecs.write_storage::<Energy>();
+    let mut entity_moved = ecs.write_storage::<EntityMoved>();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut renderables = ecs.write_storage::<Renderable>();
 
-    let map = ecs.fetch::<Map>();
+    let mut map = ecs.fetch_mut::<Map>();
 
+    let mut acted = false;
     for (entity, _player, pos, viewshed) in
         (&entities, &mut players, &mut positions, &mut viewshed).join()
     {
@@ -23,12 +65,12 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             || pos.y + delta_y < 1
             || pos.y + delta_y > map.height - 1
         {
-            return;
+            return stumbled(confused, &mut energy, entity);
         }
 
         let dest_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
 
-        for potential_target in map.tile_content[dest_idx].iter() {
+        for potential_target in map.tile_content[dest_idx].clone().iter() {
             let target = combat_stats.get(*potential_target);
             if let Some(_target) = target {
                 wants_to_melee
@@ -39,23 +81,50 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
                         },
                     )
                     .expect("Add target failed");
+                return true;
+            }
+            if let Some(door) = doors.get_mut(*potential_target) {
+                if !door.open {
+                    // Opening the door is the whole move; the player steps through next turn
+                    let renderable = renderables.get_mut(*potential_target).unwrap();
+                    door.set_open(true, renderable, &mut map, dest_idx);
+                    Energy::spend(&mut energy, entity, MOVE_COST);
+                    return true;
+                }
             }
         }
 
         if !map.blocked[dest_idx] {
-            pos.x = min(MAP_WIDTH as i32 - 1, max(0, pos.x + delta_x));
-            pos.y = min(49, max(0, pos.y + delta_y));
+            pos.x = (pos.x + delta_x).clamp(0, map.width - 1);
+            pos.y = (pos.y + delta_y).clamp(0, map.height - 1);
 
             let mut player_pos = ecs.write_resource::<Point>();
             player_pos.x = pos.x;
             player_pos.y = pos.y;
 
             viewshed.dirty = true;
+            entity_moved
+                .insert(entity, EntityMoved {})
+                .expect("Unable to insert marker");
+            Energy::spend(&mut energy, entity, MOVE_COST);
+            acted = true;
+        } else {
+            acted = stumbled(confused, &mut energy, entity);
         }
     }
+    acted
 }
 
-fn get_item(ecs: &mut World) {
+/// A confused player who lurches into a wall has still lost the turn to it.
+fn stumbled(confused: bool, energy: &mut WriteStorage<Energy>, player: Entity) -> bool {
+    if confused {
+        Energy::spend(energy, player, MOVE_COST);
+    }
+    confused
+}
+
+/// Asks to pick up the item under the player, returning false if there is none.
+pub fn get_item(ecs: &mut World) -> bool {
     let player_pos = ecs.fetch::<Point>();
     let player_entity = ecs.fetch::<Entity>();
     let entities = ecs.entities();
@@ -71,9 +140,12 @@ fn get_item(ecs: &mut World) {
     }
 
     match target_item {
-        None => gamelog
-            .entries
-            .push("There is nothing here to pick up.".to_string()),
+        None => {
+            gamelog
+                .entries
+                .push("There is nothing here to pick up.".to_string());
+            false
+        }
         Some(item) => {
             let mut pickup = ecs.write_storage::<WantsToPickupItem>();
             pickup
@@ -85,32 +157,129 @@ fn get_item(ecs: &mut World) {
                     },
                 )
                 .expect("Unable to insert want to pickup");
+            true
         }
     }
 }
 
-pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    match ctx.key {
+pub fn try_next_level(ecs: &mut World) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+    if map.tiles[player_idx] == TileType::DownStairs {
+        true
+    } else {
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+        gamelog
+            .entries
+            .push("There is no way down from here.".to_string());
+        false
+    }
+}
+
+/// Closes an open door next to the player, passing over any with something standing in it.
+pub fn try_close_door(ecs: &mut World) -> bool {
+    let player_pos = *ecs.fetch::<Point>();
+    let player_entity = *ecs.fetch::<Entity>();
+    let positions = ecs.read_storage::<Position>();
+    let entities = ecs.entities();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+    let mut energy = ecs.write_storage::<Energy>();
+    let mut map = ecs.fetch_mut::<Map>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let mut blocked = false;
+    for (door_entity, door, pos, renderable) in
+        (&entities, &mut doors, &positions, &mut renderables).join()
+    {
+        let adjacent = (pos.x - player_pos.x).abs() <= 1 && (pos.y - player_pos.y).abs() <= 1;
+        if !door.open || !adjacent {
+            continue;
+        }
+        let idx = map.xy_idx(pos.x, pos.y);
+        if map.tile_content[idx].iter().any(|e| *e != door_entity) {
+            blocked = true;
+            continue;
+        }
+        door.set_open(false, renderable, &mut map, idx);
+        Energy::spend(&mut energy, player_entity, MOVE_COST);
+        gamelog.entries.push("You close the door.".to_string());
+        return true;
+    }
+
+    let message = if blocked {
+        "Something is in the way of the door."
+    } else {
+        "There is no open door next to you."
+    };
+    gamelog.entries.push(message.to_string());
+    false
+}
+
+/// Applies the stat picked from the level-up menu.
+pub fn improve_stat(ecs: &mut World, stat: LevelUpStat) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut combat_stats = ecs.write_storage::<CombatStats>();
+    if let Some(stats) = combat_stats.get_mut(player_entity) {
+        match stat {
+            LevelUpStat::Power => stats.power += 1,
+            LevelUpStat::Defense => stats.defense += 1,
+            LevelUpStat::Health => {
+                stats.max_hp += 5;
+                stats.hp += 5;
+            }
+        }
+    }
+}
+
+pub fn player_input(gs: &mut State, key: Option<VirtualKeyCode>) -> RunState {
+    match key {
         None => return RunState::AwaitingInput,
         Some(key) => match key {
             VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => {
-                try_move_player(-1, 0, &mut gs.ecs)
+                if !try_move_player(-1, 0, &mut gs.ecs) {
+                    return RunState::AwaitingInput;
+                }
             }
 
             VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => {
-                try_move_player(1, 0, &mut gs.ecs)
+                if !try_move_player(1, 0, &mut gs.ecs) {
+                    return RunState::AwaitingInput;
+                }
             }
 
             VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => {
-                try_move_player(0, -1, &mut gs.ecs)
+                if !try_move_player(0, -1, &mut gs.ecs) {
+                    return RunState::AwaitingInput;
+                }
             }
 
             VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => {
-                try_move_player(0, 1, &mut gs.ecs)
+                if !try_move_player(0, 1, &mut gs.ecs) {
+                    return RunState::AwaitingInput;
+                }
+            }
+            VirtualKeyCode::G => {
+                if !get_item(&mut gs.ecs) {
+                    return RunState::AwaitingInput;
+                }
             }
-            VirtualKeyCode::G => get_item(&mut gs.ecs),
             VirtualKeyCode::I => return RunState::ShowInventory,
             VirtualKeyCode::D => return RunState::ShowDropItem,
+            VirtualKeyCode::R => return RunState::ShowRemoveItem,
+            VirtualKeyCode::C => {
+                if !try_close_door(&mut gs.ecs) {
+                    return RunState::AwaitingInput;
+                }
+            }
+            VirtualKeyCode::Escape => return RunState::SaveGame,
+            VirtualKeyCode::Period => {
+                if try_next_level(&mut gs.ecs) {
+                    return RunState::NextLevel;
+                }
+                return RunState::AwaitingInput;
+            }
 
             _ => return RunState::AwaitingInput,
         },
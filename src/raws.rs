@@ -0,0 +1,429 @@
+use super::random_table::RandomTable;
+use super::turn_system::NORMAL_SPEED;
+use super::{
+    Ai, AiState, AreaOfEffect, BlocksTile, CombatStats, Consumable, DamageType, DefenseBonus,
+    Energy, EntryTrigger, EquipmentSlot, Equippable, Hidden, Immune, InflictsDamage,
+    InflictsStatus, Item, MeleePowerBonus, MeleeWeapon, Monster, Name, Position, ProvidesFood,
+    ProvidesHealing, Ranged, Renderable, Resistant, SerializeMe, SingleActivation, SpeedBonus,
+    StatusKind, Viewshed, Vulnerable, XpValue,
+};
+use rltk::{console, DiceType, RGB};
+use serde::Deserialize;
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use std::collections::HashMap;
+
+/// Where designers edit entity definitions. Native builds read it at startup, so changes
+/// don't need a recompile; the copy embedded at build time is used when it can't be read.
+const RAW_FILE: &str = "./raws/spawns.json";
+const EMBEDDED_RAWS: &str = include_str!("../raws/spawns.json");
+
+#[derive(Deserialize, Debug)]
+pub struct Raws {
+    pub mobs: Vec<MobDef>,
+    pub items: Vec<ItemDef>,
+    #[serde(default)]
+    pub traps: Vec<TrapDef>,
+    pub spawn_table: Vec<SpawnTableEntry>,
+}
+
+/// A chance of `name` spawning on levels `min_depth..=max_depth`. A name can appear in several
+/// entries to give it different weights at different depths.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpawnTableEntry {
+    pub name: String,
+    pub weight: i32,
+    pub min_depth: i32,
+    pub max_depth: i32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RenderableDef {
+    pub glyph: String,
+    pub fg: String,
+    pub bg: String,
+    pub order: i32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StatsDef {
+    pub max_hp: i32,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MobDef {
+    pub name: String,
+    pub renderable: RenderableDef,
+    pub blocks_tile: bool,
+    pub vision_range: i32,
+    pub stats: StatsDef,
+    #[serde(default)]
+    pub xp_value: i32,
+    #[serde(default = "normal_speed")]
+    pub speed: i32,
+    /// Damage dice for fighting without a weapon, such as `1d6`.
+    #[serde(default = "unarmed_damage")]
+    pub damage: String,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    #[serde(default)]
+    pub resistances: ResistancesDef,
+    /// The percentage chance of starting out asleep when spawned with a level.
+    #[serde(default)]
+    pub sleep_chance: i32,
+}
+
+fn normal_speed() -> i32 {
+    NORMAL_SPEED
+}
+
+fn unarmed_damage() -> String {
+    "1d4".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StatusDef {
+    pub kind: StatusKind,
+    pub turns: i32,
+}
+
+/// Damage types halved, doubled or ignored by a monster or by whoever wears a piece of armour.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ResistancesDef {
+    pub resistant: Vec<DamageType>,
+    pub vulnerable: Vec<DamageType>,
+    pub immune: Vec<DamageType>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct EffectsDef {
+    pub provides_healing: Option<i32>,
+    pub ranged: Option<i32>,
+    pub damage: Option<i32>,
+    pub damage_type: DamageType,
+    pub area_of_effect: Option<i32>,
+    pub status: Option<StatusDef>,
+    pub provides_food: bool,
+}
+
+/// The slot an item is worn in and what it adds while worn.
+#[derive(Deserialize, Debug, Clone)]
+pub struct EquippableDef {
+    pub slot: EquipmentSlot,
+    /// Damage dice rolled by a weapon, such as `1d8+1`.
+    #[serde(default)]
+    pub damage: Option<String>,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    #[serde(default)]
+    pub power_bonus: Option<i32>,
+    #[serde(default)]
+    pub defense_bonus: Option<i32>,
+    #[serde(default)]
+    pub speed_bonus: Option<i32>,
+    #[serde(default)]
+    pub resistances: ResistancesDef,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ItemDef {
+    pub name: String,
+    pub renderable: RenderableDef,
+    #[serde(default)]
+    pub consumable: bool,
+    #[serde(default)]
+    pub effects: EffectsDef,
+    #[serde(default)]
+    pub equippable: Option<EquippableDef>,
+}
+
+/// Something on the floor that fires its `effects` at whatever steps on it.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrapDef {
+    pub name: String,
+    pub renderable: RenderableDef,
+    #[serde(default)]
+    pub hidden: bool,
+    #[serde(default)]
+    pub single_activation: bool,
+    #[serde(default)]
+    pub effects: EffectsDef,
+}
+
+/// Registry of every entity definition, looked up by name when spawning.
+pub struct RawMaster {
+    raws: Raws,
+    mob_index: HashMap<String, usize>,
+    item_index: HashMap<String, usize>,
+    trap_index: HashMap<String, usize>,
+}
+
+impl RawMaster {
+    pub fn new(raws: Raws) -> RawMaster {
+        let mut mob_index = HashMap::new();
+        for (i, mob) in raws.mobs.iter().enumerate() {
+            mob_index.insert(mob.name.clone(), i);
+        }
+        let mut item_index = HashMap::new();
+        for (i, item) in raws.items.iter().enumerate() {
+            item_index.insert(item.name.clone(), i);
+        }
+        let mut trap_index = HashMap::new();
+        for (i, trap) in raws.traps.iter().enumerate() {
+            trap_index.insert(trap.name.clone(), i);
+        }
+
+        RawMaster {
+            raws,
+            mob_index,
+            item_index,
+            trap_index,
+        }
+    }
+
+    pub fn mob(&self, name: &str) -> Option<&MobDef> {
+        self.mob_index.get(name).map(|i| &self.raws.mobs[*i])
+    }
+
+    pub fn item(&self, name: &str) -> Option<&ItemDef> {
+        self.item_index.get(name).map(|i| &self.raws.items[*i])
+    }
+
+    pub fn trap(&self, name: &str) -> Option<&TrapDef> {
+        self.trap_index.get(name).map(|i| &self.raws.traps[*i])
+    }
+
+    /// The spawn table for monsters on level `depth`.
+    pub fn monster_table(&self, depth: i32) -> RandomTable {
+        self.spawn_table(depth, |name| self.mob_index.contains_key(name))
+    }
+
+    /// The spawn table for items on level `depth`.
+    pub fn item_table(&self, depth: i32) -> RandomTable {
+        self.spawn_table(depth, |name| self.item_index.contains_key(name))
+    }
+
+    /// The spawn table for traps on level `depth`.
+    pub fn trap_table(&self, depth: i32) -> RandomTable {
+        self.spawn_table(depth, |name| self.trap_index.contains_key(name))
+    }
+
+    fn spawn_table<F: Fn(&str) -> bool>(&self, depth: i32, include: F) -> RandomTable {
+        self.raws
+            .spawn_table
+            .iter()
+            .filter(|e| depth >= e.min_depth && depth <= e.max_depth && include(&e.name))
+            .fold(RandomTable::new(), |table, e| table.add(&e.name, e.weight))
+    }
+}
+
+pub fn load_raws() -> RawMaster {
+    let data = std::fs::read_to_string(RAW_FILE).unwrap_or_else(|_| EMBEDDED_RAWS.to_string());
+    let raws: Raws = serde_json::from_str(&data).expect("Unable to parse raw entity definitions");
+    RawMaster::new(raws)
+}
+
+fn dice(def: &str) -> DiceType {
+    rltk::parse_dice_string(def).expect("Invalid dice string")
+}
+
+fn with_resistances<'a>(mut eb: EntityBuilder<'a>, def: &ResistancesDef) -> EntityBuilder<'a> {
+    if !def.resistant.is_empty() {
+        eb = eb.with(Resistant {
+            types: def.resistant.clone(),
+        });
+    }
+    if !def.vulnerable.is_empty() {
+        eb = eb.with(Vulnerable {
+            types: def.vulnerable.clone(),
+        });
+    }
+    if !def.immune.is_empty() {
+        eb = eb.with(Immune {
+            types: def.immune.clone(),
+        });
+    }
+    eb
+}
+
+fn renderable(def: &RenderableDef) -> Renderable {
+    Renderable {
+        glyph: rltk::to_cp437(def.glyph.chars().next().unwrap_or('?')),
+        fg: RGB::from_hex(&def.fg).expect("Invalid foreground colour"),
+        bg: RGB::from_hex(&def.bg).expect("Invalid background colour"),
+        render_order: def.order,
+    }
+}
+
+/// Builds the mob, item or trap called `name` at `(x, y)`, returning `None` if no definition has
+/// that name.
+pub fn spawn_named_entity(ecs: &mut World, name: &str, x: i32, y: i32) -> Option<Entity> {
+    let (mob, item, trap) = {
+        let raws = ecs.fetch::<RawMaster>();
+        (
+            raws.mob(name).cloned(),
+            raws.item(name).cloned(),
+            raws.trap(name).cloned(),
+        )
+    };
+
+    if let Some(mob) = mob {
+        return Some(spawn_mob(ecs, &mob, x, y));
+    }
+    if let Some(item) = item {
+        return Some(spawn_item(ecs, &item, x, y));
+    }
+    if let Some(trap) = trap {
+        return Some(spawn_trap(ecs, &trap, x, y));
+    }
+
+    console::log(format!("No raw definition for {}", name));
+    None
+}
+
+fn spawn_mob(ecs: &mut World, mob: &MobDef, x: i32, y: i32) -> Entity {
+    let mut eb = ecs
+        .create_entity()
+        .with(Position { x, y })
+        .with(renderable(&mob.renderable))
+        .with(Viewshed {
+            visible_tiles: Vec::new(),
+            range: mob.vision_range,
+            dirty: true,
+        })
+        .with(Monster {})
+        .with(Ai {
+            state: AiState::Wandering,
+        })
+        .with(Energy {
+            speed: mob.speed,
+            current: 0,
+        })
+        .with(Name {
+            name: mob.name.clone(),
+        })
+        .with(CombatStats {
+            max_hp: mob.stats.max_hp,
+            hp: mob.stats.hp,
+            defense: mob.stats.defense,
+            power: mob.stats.power,
+        })
+        .with(MeleeWeapon {
+            damage: dice(&mob.damage),
+            damage_type: mob.damage_type,
+        });
+
+    if mob.blocks_tile {
+        eb = eb.with(BlocksTile {});
+    }
+    if mob.xp_value > 0 {
+        eb = eb.with(XpValue {
+            amount: mob.xp_value,
+        });
+    }
+    eb = with_resistances(eb, &mob.resistances);
+
+    eb.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+fn spawn_item(ecs: &mut World, item: &ItemDef, x: i32, y: i32) -> Entity {
+    let mut eb = ecs
+        .create_entity()
+        .with(Position { x, y })
+        .with(renderable(&item.renderable))
+        .with(Name {
+            name: item.name.clone(),
+        })
+        .with(Item {});
+
+    if item.consumable {
+        eb = eb.with(Consumable {});
+    }
+
+    let effects = &item.effects;
+    if let Some(heal_amount) = effects.provides_healing {
+        eb = eb.with(ProvidesHealing { heal_amount });
+    }
+    if let Some(range) = effects.ranged {
+        eb = eb.with(Ranged { range });
+    }
+    if let Some(damage) = effects.damage {
+        eb = eb.with(InflictsDamage {
+            damage,
+            damage_type: effects.damage_type,
+        });
+    }
+    if let Some(radius) = effects.area_of_effect {
+        eb = eb.with(AreaOfEffect { radius });
+    }
+    if let Some(status) = &effects.status {
+        eb = eb.with(InflictsStatus {
+            kind: status.kind,
+            turns: status.turns,
+        });
+    }
+    if effects.provides_food {
+        eb = eb.with(ProvidesFood {});
+    }
+
+    if let Some(equippable) = &item.equippable {
+        eb = eb.with(Equippable {
+            slot: equippable.slot,
+        });
+        if let Some(damage) = &equippable.damage {
+            eb = eb.with(MeleeWeapon {
+                damage: dice(damage),
+                damage_type: equippable.damage_type,
+            });
+        }
+        if let Some(power) = equippable.power_bonus {
+            eb = eb.with(MeleePowerBonus { power });
+        }
+        if let Some(defense) = equippable.defense_bonus {
+            eb = eb.with(DefenseBonus { defense });
+        }
+        if let Some(speed) = equippable.speed_bonus {
+            eb = eb.with(SpeedBonus { speed });
+        }
+        eb = with_resistances(eb, &equippable.resistances);
+    }
+
+    eb.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+fn spawn_trap(ecs: &mut World, trap: &TrapDef, x: i32, y: i32) -> Entity {
+    let mut eb = ecs
+        .create_entity()
+        .with(Position { x, y })
+        .with(renderable(&trap.renderable))
+        .with(Name {
+            name: trap.name.clone(),
+        })
+        .with(EntryTrigger {});
+
+    if trap.hidden {
+        eb = eb.with(Hidden {});
+    }
+    if trap.single_activation {
+        eb = eb.with(SingleActivation {});
+    }
+    if let Some(damage) = trap.effects.damage {
+        eb = eb.with(InflictsDamage {
+            damage,
+            damage_type: trap.effects.damage_type,
+        });
+    }
+    if let Some(status) = &trap.effects.status {
+        eb = eb.with(InflictsStatus {
+            kind: status.kind,
+            turns: status.turns,
+        });
+    }
+
+    eb.marked::<SimpleMarker<SerializeMe>>().build()
+}
@@ -0,0 +1,28 @@
+use std::path::{Path, PathBuf};
+
+/// Where the game keeps what outlives a session: the saved game, the hall of fame and the morgue
+/// files. Kept as a resource so the headless harness can point them away from the player's own.
+#[derive(Clone)]
+pub struct RecordPaths {
+    pub save_file: PathBuf,
+    pub high_score_file: PathBuf,
+    pub morgue_dir: PathBuf,
+}
+
+impl RecordPaths {
+    /// Keeps the save and both records inside `dir`.
+    pub fn in_dir(dir: &Path) -> RecordPaths {
+        RecordPaths {
+            save_file: dir.join("savegame.json"),
+            high_score_file: dir.join("highscores.json"),
+            morgue_dir: dir.join("morgue"),
+        }
+    }
+}
+
+impl Default for RecordPaths {
+    /// Next to the game, in the working directory.
+    fn default() -> RecordPaths {
+        RecordPaths::in_dir(Path::new("."))
+    }
+}
@@ -0,0 +1,286 @@
+use super::args::arg_value;
+use super::RunState;
+use rltk::{console, Rltk, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The keyboard and mouse state handed to an input-consuming `RunState` on one frame.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct RecordedInput {
+    pub key: Option<VirtualKeyCode>,
+    /// Console cell under the mouse, so replays don't depend on the window size.
+    pub mouse_pos: (i32, i32),
+    pub left_click: bool,
+}
+
+/// Everything needed to reproduce a run: the seed it started from, the map builder it asked for,
+/// and every input it consumed.
+#[derive(Clone, Default)]
+pub struct Replay {
+    pub seed: u64,
+    pub builder: Option<String>,
+    pub inputs: Vec<RecordedInput>,
+}
+
+/// The first line of a replay file. Every line after it is one `RecordedInput`.
+#[derive(Serialize, Deserialize)]
+struct ReplayHeader {
+    seed: u64,
+    #[serde(default)]
+    builder: Option<String>,
+}
+
+impl Replay {
+    /// Reads back a replay file written while recording.
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read replay file {}: {}", path.display(), e))?;
+        let unparsable =
+            |e: serde_json::Error| format!("Unable to parse replay file {}: {}", path.display(), e);
+        let mut lines = data.lines();
+        let header: ReplayHeader =
+            serde_json::from_str(lines.next().unwrap_or_default()).map_err(unparsable)?;
+        let inputs = lines
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()
+            .map_err(unparsable)?;
+        Ok(Replay {
+            seed: header.seed,
+            builder: header.builder,
+            inputs,
+        })
+    }
+}
+
+pub enum ReplayMode {
+    Off,
+    /// Writes each run started from the main menu to a file of its own: the first to `path`,
+    /// later ones numbered after it.
+    Recording {
+        path: String,
+        runs: u32,
+        file: Option<File>,
+    },
+    Playing {
+        replay: Replay,
+        next: usize,
+        speed: usize,
+    },
+}
+
+/// Only these states read the keyboard and mouse during play, so only they are recorded and fed.
+fn consumes_input(runstate: RunState) -> bool {
+    matches!(
+        runstate,
+        RunState::AwaitingInput
+            | RunState::ShowInventory
+            | RunState::ShowDropItem
+            | RunState::ShowRemoveItem
+            | RunState::ShowTargeting { .. }
+            | RunState::LevelUp
+    )
+}
+
+/// Screens a run only reaches once it is over, which no replay drives.
+fn waits_for_player(runstate: RunState) -> bool {
+    matches!(
+        runstate,
+        RunState::GameOver | RunState::MainMenu { .. } | RunState::ShowHighScores
+    )
+}
+
+impl ReplayMode {
+    /// Picks the mode from `--record <file>` or `--replay <file> [--replay-speed <n>]`. A replay
+    /// that can't be read, or a speed that isn't a positive number, is an error.
+    pub fn from_args() -> Result<ReplayMode, String> {
+        if let Some(path) = arg_value("--replay") {
+            let speed = match arg_value("--replay-speed") {
+                Some(speed) => match speed.parse() {
+                    Ok(speed) if speed > 0 => speed,
+                    _ => {
+                        return Err(format!(
+                            "--replay-speed expects a whole number above 0, not '{}'",
+                            speed
+                        ))
+                    }
+                },
+                None => 1,
+            };
+            return Ok(ReplayMode::playing(Replay::load(Path::new(&path))?, speed));
+        }
+
+        if let Some(path) = arg_value("--record") {
+            return Ok(ReplayMode::recording(path));
+        }
+
+        Ok(ReplayMode::Off)
+    }
+
+    /// Records to `path`; nothing is written until a run starts.
+    pub fn recording(path: String) -> ReplayMode {
+        ReplayMode::Recording {
+            path,
+            runs: 0,
+            file: None,
+        }
+    }
+
+    /// Plays `replay` back, running `speed` game steps per frame.
+    pub fn playing(replay: Replay, speed: usize) -> ReplayMode {
+        ReplayMode::Playing {
+            replay,
+            next: 0,
+            speed: usize::max(1, speed),
+        }
+    }
+
+    /// The seed a replay has to start from, if one is being played back.
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            ReplayMode::Playing { replay, .. } => Some(replay.seed),
+            _ => None,
+        }
+    }
+
+    /// The map builder a replay has to use, if one is being played back and it asked for one.
+    pub fn builder(&self) -> Option<String> {
+        match self {
+            ReplayMode::Playing { replay, .. } => replay.builder.clone(),
+            _ => None,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self, ReplayMode::Playing { .. })
+    }
+
+    /// How many game steps to run per rendered frame; accelerated playback runs several.
+    pub fn steps_per_tick(&self) -> usize {
+        match self {
+            ReplayMode::Playing { speed, .. } => *speed,
+            _ => 1,
+        }
+    }
+
+    /// Starts recording a run begun from `seed` and `builder` in a new file, returning its path.
+    pub fn start_recording(&mut self, seed: u64, builder: Option<String>) -> Option<PathBuf> {
+        if let ReplayMode::Recording { path, runs, file } = self {
+            *runs += 1;
+            let run_path = run_path(Path::new(path), *runs);
+            let mut new_file = File::create(&run_path).expect("Unable to create replay file");
+            write_line(&mut new_file, &ReplayHeader { seed, builder });
+            *file = Some(new_file);
+            return Some(run_path);
+        }
+        None
+    }
+
+    /// Stops writing the current run, e.g. at Game Over, so that nothing after it lands in its
+    /// file. The next new game starts a file of its own.
+    pub fn finish_recording(&mut self) {
+        if let ReplayMode::Recording { file, .. } = self {
+            *file = None;
+        }
+    }
+
+    /// While playing back, the next recorded input if `runstate` reads one. Playback ends, and
+    /// control goes back to the player, once the inputs run out or the run reaches a screen that
+    /// waits on the player, such as Game Over.
+    pub fn next_input(&mut self, runstate: RunState) -> Option<RecordedInput> {
+        let mut finished = false;
+        let mut input = None;
+        if let ReplayMode::Playing { replay, next, .. } = self {
+            if waits_for_player(runstate) {
+                finished = true;
+            } else if consumes_input(runstate) {
+                input = replay.inputs.get(*next).copied();
+                match input {
+                    Some(_) => *next += 1,
+                    None => finished = true,
+                }
+            }
+        }
+
+        if finished {
+            console::log("Replay finished, handing control back to the player");
+            *self = ReplayMode::Off;
+        }
+        input
+    }
+
+    /// While playing back, replaces the frame's real input with the next recorded one. Once
+    /// playback ends the real input goes through untouched.
+    pub fn feed_input(&mut self, runstate: RunState, ctx: &mut Rltk) {
+        if !self.is_playing() {
+            return;
+        }
+        match self.next_input(runstate) {
+            Some(input) => {
+                ctx.key = input.key;
+                set_mouse_cell(ctx, input.mouse_pos);
+                ctx.left_click = input.left_click;
+            }
+            None if self.is_playing() => {
+                ctx.key = None;
+                ctx.left_click = false;
+            }
+            None => {}
+        }
+    }
+
+    /// While recording, stores the input an input-consuming state was just given.
+    pub fn record_input(&mut self, runstate: RunState, ctx: &Rltk) {
+        self.record(
+            runstate,
+            RecordedInput {
+                key: ctx.key,
+                mouse_pos: ctx.mouse_pos(),
+                left_click: ctx.left_click,
+            },
+        );
+    }
+
+    /// Appends `input` to the run being recorded, if `runstate` read it. Each input is written as
+    /// it happens, so a crash still leaves a complete replay behind.
+    pub fn record(&mut self, runstate: RunState, input: RecordedInput) {
+        if let ReplayMode::Recording {
+            file: Some(file), ..
+        } = self
+        {
+            if consumes_input(runstate) && (input.key.is_some() || input.left_click) {
+                write_line(file, &input);
+            }
+        }
+    }
+}
+
+/// `Rltk` stores the mouse in window pixels, so aim at the middle of the recorded cell.
+fn set_mouse_cell(ctx: &mut Rltk, cell: (i32, i32)) {
+    let (width, height) = ctx.get_char_size();
+    let cell_width = ctx.width_pixels as f32 / width as f32;
+    let cell_height = ctx.height_pixels as f32 / height as f32;
+    ctx.mouse_pos = (
+        ((cell.0 as f32 + 0.5) * cell_width) as i32,
+        ((cell.1 as f32 + 0.5) * cell_height) as i32,
+    );
+}
+
+/// `path` for a session's first run, then `<stem>-<run>.<extension>` beside it.
+fn run_path(path: &Path, run: u32) -> PathBuf {
+    if run == 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, run, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, run),
+    };
+    path.with_file_name(name)
+}
+
+fn write_line<T: Serialize>(file: &mut File, value: &T) {
+    let line = serde_json::to_string(value).expect("Unable to serialize replay");
+    writeln!(file, "{}", line).expect("Unable to write replay file");
+}
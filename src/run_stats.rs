@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// Running totals for the current game, reported in the morgue file when it ends.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RunStats {
+    /// Turns the player has been given so far.
+    pub turns: i32,
+    /// The name of every monster the player has killed, in the order they died.
+    pub kills: Vec<String>,
+    /// What dealt the player's fatal hit, once they have died.
+    pub cause_of_death: Option<String>,
+}
@@ -0,0 +1,247 @@
+use super::components::*;
+use super::gamelog::GameLog;
+use super::map::Map;
+use super::record_paths::RecordPaths;
+use super::run_stats::RunStats;
+use super::seed::GameSeed;
+use rltk::RandomNumberGenerator;
+use specs::error::NoError;
+use specs::prelude::*;
+use specs::saveload::{
+    DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker, SimpleMarkerAllocator,
+};
+use std::fs;
+use std::fs::File;
+
+macro_rules! serialize_individually {
+    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        SerializeComponents::<NoError, SimpleMarker<SerializeMe>>::serialize(
+            &( $ecs.read_storage::<$type>(), ),
+            &$data.0,
+            &$data.1,
+            &mut $ser,
+        )
+        .unwrap();
+        )*
+    };
+}
+
+macro_rules! deserialize_individually {
+    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        DeserializeComponents::<NoError, _>::deserialize(
+            &mut ( &mut $ecs.write_storage::<$type>(), ),
+            &$data.0, // entities
+            &mut $data.1, // marker
+            &mut $data.2, // allocater
+            &mut $de,
+        )
+        .unwrap();
+        )*
+    };
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_game(_ecs: &mut World) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_game(ecs: &mut World) {
+    // Create helper
+    let mapcopy = ecs.get_mut::<Map>().unwrap().clone();
+    let logcopy = ecs.get_mut::<GameLog>().unwrap().clone();
+    let runstate = *ecs.fetch::<RunState>();
+    let seed = *ecs.fetch::<GameSeed>();
+    let rng = (*ecs.fetch::<RandomNumberGenerator>()).clone();
+    let run_stats = (*ecs.fetch::<RunStats>()).clone();
+    let savehelper = ecs
+        .create_entity()
+        .with(SerializationHelper {
+            map: mapcopy,
+            log: logcopy,
+            runstate,
+            seed,
+            rng,
+            run_stats,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    // Actually serialize
+    {
+        let data = (
+            ecs.entities(),
+            ecs.read_storage::<SimpleMarker<SerializeMe>>(),
+        );
+
+        let writer = File::create(&ecs.fetch::<RecordPaths>().save_file).unwrap();
+        let mut serializer = serde_json::Serializer::new(writer);
+        serialize_individually!(
+            ecs,
+            serializer,
+            data,
+            Position,
+            Renderable,
+            LeftMover,
+            Player,
+            Viewshed,
+            Monster,
+            Ai,
+            Name,
+            BlocksTile,
+            Door,
+            CombatStats,
+            WantsToMelee,
+            SufferDamage,
+            Item,
+            Consumable,
+            ProvidesHealing,
+            InBackpack,
+            WantsToPickupItem,
+            WantsToDropItem,
+            WantsToUseItem,
+            Ranged,
+            InflictsDamage,
+            AreaOfEffect,
+            StatusEffect,
+            InflictsStatus,
+            EntryTrigger,
+            Hidden,
+            SingleActivation,
+            EntityMoved,
+            Equippable,
+            Equipped,
+            MeleePowerBonus,
+            MeleeWeapon,
+            Resistant,
+            Vulnerable,
+            Immune,
+            DefenseBonus,
+            WantsToRemoveItem,
+            Experience,
+            XpValue,
+            ProvidesFood,
+            HungerClock,
+            Energy,
+            MyTurn,
+            SpeedBonus,
+            SerializationHelper
+        );
+    }
+
+    // Clean up
+    ecs.delete_entity(savehelper).expect("Crash on cleanup");
+}
+
+pub fn does_save_exist(paths: &RecordPaths) -> bool {
+    paths.save_file.exists()
+}
+
+pub fn load_game(ecs: &mut World) {
+    {
+        // Delete everything
+        let mut to_delete = Vec::new();
+        for e in ecs.entities().join() {
+            to_delete.push(e);
+        }
+        for del in to_delete.iter() {
+            ecs.delete_entity(*del).expect("Deletion failed");
+        }
+    }
+
+    let data = fs::read_to_string(&ecs.fetch::<RecordPaths>().save_file).unwrap();
+    let mut de = serde_json::Deserializer::from_str(&data);
+
+    {
+        let mut d = (
+            &mut ecs.entities(),
+            &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(),
+            &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>(),
+        );
+
+        deserialize_individually!(
+            ecs,
+            de,
+            d,
+            Position,
+            Renderable,
+            LeftMover,
+            Player,
+            Viewshed,
+            Monster,
+            Ai,
+            Name,
+            BlocksTile,
+            Door,
+            CombatStats,
+            WantsToMelee,
+            SufferDamage,
+            Item,
+            Consumable,
+            ProvidesHealing,
+            InBackpack,
+            WantsToPickupItem,
+            WantsToDropItem,
+            WantsToUseItem,
+            Ranged,
+            InflictsDamage,
+            AreaOfEffect,
+            StatusEffect,
+            InflictsStatus,
+            EntryTrigger,
+            Hidden,
+            SingleActivation,
+            EntityMoved,
+            Equippable,
+            Equipped,
+            MeleePowerBonus,
+            MeleeWeapon,
+            Resistant,
+            Vulnerable,
+            Immune,
+            DefenseBonus,
+            WantsToRemoveItem,
+            Experience,
+            XpValue,
+            ProvidesFood,
+            HungerClock,
+            Energy,
+            MyTurn,
+            SpeedBonus,
+            SerializationHelper
+        );
+    }
+
+    let mut deleteme: Option<Entity> = None;
+    {
+        let entities = ecs.entities();
+        let helper = ecs.read_storage::<SerializationHelper>();
+        let player = ecs.read_storage::<Player>();
+        let position = ecs.read_storage::<Position>();
+        for (e, h) in (&entities, &helper).join() {
+            let mut worldmap = ecs.write_resource::<Map>();
+            *worldmap = h.map.clone();
+            worldmap.tile_content = vec![Vec::new(); worldmap.tiles.len()];
+            *ecs.write_resource::<GameLog>() = h.log.clone();
+            *ecs.write_resource::<RunState>() = h.runstate;
+            *ecs.write_resource::<GameSeed>() = h.seed;
+            *ecs.write_resource::<RandomNumberGenerator>() = h.rng.clone();
+            *ecs.write_resource::<RunStats>() = h.run_stats.clone();
+            deleteme = Some(e);
+        }
+        for (e, _p, pos) in (&entities, &player, &position).join() {
+            let mut ppos = ecs.write_resource::<rltk::Point>();
+            *ppos = rltk::Point::new(pos.x, pos.y);
+            let mut player_resource = ecs.write_resource::<Entity>();
+            *player_resource = e;
+        }
+    }
+    ecs.delete_entity(deleteme.unwrap())
+        .expect("Unable to delete helper");
+}
+
+pub fn delete_save(paths: &RecordPaths) {
+    if does_save_exist(paths) {
+        std::fs::remove_file(&paths.save_file).expect("Unable to delete file");
+    }
+}
@@ -0,0 +1,26 @@
+use super::args::arg_value;
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+/// The seed a run's `RandomNumberGenerator` was created from. Map generation, spawning and
+/// combat all roll on that one generator, so the same seed and inputs replay the same game.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct GameSeed {
+    pub seed: u64,
+}
+
+/// Reads the seed from a `--seed <number>` command-line argument, if one was given. Anything but a
+/// number is an error rather than quietly falling back to a random seed.
+pub fn seed_from_args() -> Result<Option<u64>, String> {
+    match arg_value("--seed") {
+        Some(seed) => seed
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("--seed expects a whole number, not '{}'", seed)),
+        None => Ok(None),
+    }
+}
+
+pub fn random_seed() -> u64 {
+    RandomNumberGenerator::new().next_u64()
+}
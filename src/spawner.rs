@@ -1,12 +1,17 @@
+use super::raws::{spawn_named_entity, RawMaster};
+use super::turn_system::{NORMAL_SPEED, TURN_THRESHOLD};
+use super::SerializeMe;
 use super::{
-    AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, InflictsDamage, Item, Monster,
-    Name, Player, Position, ProvidesHealing, Ranged, Rect, Renderable, Viewshed, MAP_WIDTH,
+    Ai, AiState, CombatStats, DamageType, Door, Energy, Experience, HungerClock, HungerState, Map,
+    MeleeWeapon, Name, Player, Position, Rect, Renderable, TileType, Viewshed,
 };
-use rltk::{RandomNumberGenerator, RGB};
+use rltk::{DiceType, RandomNumberGenerator, RGB};
 use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
 
 const MAX_MONSTERS: i32 = 4;
 const MAX_ITEMS: i32 = 2;
+const MAX_TRAPS: i32 = 1;
 
 pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
     ecs.create_entity()
@@ -35,190 +40,152 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
             defense: 2,
             power: 5,
         })
-        .build()
-}
-
-pub fn random_monster(ecs: &mut World, x: i32, y: i32) {
-    let roll: i32;
-    {
-        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        roll = rng.roll_dice(1, 2);
-    }
-    match roll {
-        1 => orc(ecs, x, y),
-        _ => goblin(ecs, x, y),
-    }
-}
-
-pub fn orc(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('o'), "Orc")
-}
-pub fn goblin(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('g'), "Goblin")
-}
-
-fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: rltk::FontCharType, name: S) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph,
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 1,
-        })
-        .with(Viewshed {
-            visible_tiles: Vec::new(),
-            range: 8,
-            dirty: true,
+        // Bare-handed until a weapon is equipped
+        .with(MeleeWeapon {
+            damage: DiceType::new(1, 4, 1),
+            damage_type: DamageType::Physical,
         })
-        .with(Monster {})
-        .with(Name {
-            name: name.to_string(),
+        .with(Experience { level: 1, xp: 0 })
+        // The player starts ready to act, ahead of the monsters
+        .with(Energy {
+            speed: NORMAL_SPEED,
+            current: TURN_THRESHOLD,
         })
-        .with(BlocksTile {})
-        .with(CombatStats {
-            max_hp: 16,
-            hp: 16,
-            defense: 1,
-            power: 4,
+        .with(HungerClock {
+            state: HungerState::WellFed,
+            duration: 20,
         })
-        .build();
-}
-
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('¡'),
-            fg: RGB::named(rltk::MAGENTA),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Health Potion".to_string(),
-        })
-        .with(Item {})
-        .with(Consumable {})
-        .with(ProvidesHealing { heal_amount: 8 })
-        .build();
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
+/// A closed door, standing in a doorway.
+pub fn door(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::CYAN),
+            glyph: rltk::to_cp437('+'),
+            fg: RGB::named(rltk::CHOCOLATE),
             bg: RGB::named(rltk::BLACK),
             render_order: 2,
         })
         .with(Name {
-            name: "Magic Missile Scroll".to_string(),
+            name: "Door".to_string(),
         })
-        .with(Item {})
-        .with(Consumable {})
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 8 })
-        .build();
+        .with(Door { open: false })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::ORANGE),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Fireball Scroll".to_string(),
-        })
-        .with(Item {})
-        .with(Consumable {})
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 30 })
-        .with(AreaOfEffect { radius: 3 })
-        .build();
-}
+/// Fills a room with monsters, items and traps rolled from the spawn tables for level `depth`.
+pub fn spawn_room(ecs: &mut World, room: &Rect, depth: i32) {
+    let mut possible_targets: Vec<usize> = Vec::new();
+    {
+        let map = ecs.fetch::<Map>();
+        for y in room.y1 + 1..=room.y2 {
+            for x in room.x1 + 1..=room.x2 {
+                let idx = map.xy_idx(x, y);
+                if map.tiles[idx] == TileType::Floor {
+                    possible_targets.push(idx);
+                }
+            }
+        }
+    }
 
-fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::PINK),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Confusion Scroll".to_string(),
-        })
-        .with(Item {})
-        .with(Consumable {})
-        .with(Ranged { range: 6 })
-        .with(Confusion { turns: 4 })
-        .build();
+    spawn_region(ecs, &possible_targets, depth);
 }
 
-fn random_item(ecs: &mut World, x: i32, y: i32) {
-    let roll: i32;
-    {
-        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        roll = rng.roll_dice(1, 3);
-    }
-    match roll {
-        1 => health_potion(ecs, x, y),
-        2 => fireball_scroll(ecs, x, y),
-        3 => confusion_scroll(ecs, x, y),
-        _ => magic_missile_scroll(ecs, x, y),
+/// Takes a random tile out of `tiles`, so that no two spawns of a kind share one.
+fn take_random_tile(rng: &mut RandomNumberGenerator, tiles: &mut Vec<usize>) -> Option<usize> {
+    if tiles.is_empty() {
+        return None;
     }
+    let i = rng.roll_dice(1, tiles.len() as i32) - 1;
+    Some(tiles.remove(i as usize))
 }
 
-pub fn spawn_room(ecs: &mut World, room: &Rect) {
-    let mut monster_spawn_points: Vec<usize> = Vec::new();
-    let mut item_spawn_points: Vec<usize> = Vec::new();
+/// Fills an area of the map, given as tile indices, with monsters, items and traps rolled from
+/// the spawn tables for level `depth`.
+pub fn spawn_region(ecs: &mut World, area: &[usize], depth: i32) {
+    // Each monster's spot, name, and whether it starts out asleep
+    let mut monster_spawn_points: Vec<(usize, String, bool)> = Vec::new();
+    let mut item_spawn_points: Vec<(usize, String)> = Vec::new();
+    let mut trap_spawn_points: Vec<(usize, String)> = Vec::new();
+    let map_width = ecs.fetch::<Map>().width as usize;
+
+    // Prefabs may have walled over part of the area or put something of their own there, and
+    // nothing random is stacked on top of a template's monster, item or trap
+    let area: Vec<usize> = {
+        let map = ecs.fetch::<Map>();
+        let positions = ecs.read_storage::<Position>();
+        let occupied: Vec<usize> = positions
+            .join()
+            .map(|pos| map.xy_idx(pos.x, pos.y))
+            .collect();
+        area.iter()
+            .filter(|idx| map.tiles[**idx] == TileType::Floor && !occupied.contains(idx))
+            .copied()
+            .collect()
+    };
 
     {
+        let raws = ecs.fetch::<RawMaster>();
+        let monster_table = raws.monster_table(depth);
+        let item_table = raws.item_table(depth);
+        let trap_table = raws.trap_table(depth);
         let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        let num_monsters = rng.roll_dice(1, MAX_MONSTERS + 2) - 3;
+        let num_monsters = rng.roll_dice(1, MAX_MONSTERS + 2) + (depth - 1) - 3;
         let num_items = rng.roll_dice(1, MAX_ITEMS + 2); /*  - 3; */
+        let num_traps = rng.roll_dice(1, MAX_TRAPS + 3) - 3;
 
+        let mut free_tiles = area.to_vec();
         for _i in 0..num_monsters {
-            let mut added = false;
-            while !added {
-                let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-                let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-                let idx = (y * MAP_WIDTH) + x;
-                if !monster_spawn_points.contains(&idx) {
-                    monster_spawn_points.push(idx);
-                    added = true;
+            if let Some(idx) = take_random_tile(&mut rng, &mut free_tiles) {
+                if let Some(name) = monster_table.roll(&mut rng) {
+                    let sleep_chance = raws.mob(&name).map_or(0, |mob| mob.sleep_chance);
+                    let asleep = rng.roll_dice(1, 100) <= sleep_chance;
+                    monster_spawn_points.push((idx, name, asleep));
                 }
             }
         }
 
+        let mut free_tiles = area.to_vec();
         for _i in 0..num_items {
-            let mut added = false;
-            while !added {
-                let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-                let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-                let idx = (y * MAP_WIDTH) + x;
-                if !item_spawn_points.contains(&idx) {
-                    item_spawn_points.push(idx);
-                    added = true;
+            if let Some(idx) = take_random_tile(&mut rng, &mut free_tiles) {
+                if let Some(name) = item_table.roll(&mut rng) {
+                    item_spawn_points.push((idx, name));
+                }
+            }
+        }
+
+        let mut free_tiles = area.to_vec();
+        for _i in 0..num_traps {
+            if let Some(idx) = take_random_tile(&mut rng, &mut free_tiles) {
+                if let Some(name) = trap_table.roll(&mut rng) {
+                    trap_spawn_points.push((idx, name));
                 }
             }
         }
     }
 
-    for idx in monster_spawn_points.iter() {
-        let x = *idx % MAP_WIDTH;
-        let y = *idx / MAP_WIDTH;
-        random_monster(ecs, x as i32, y as i32);
+    for (idx, name, asleep) in monster_spawn_points.iter() {
+        let x = *idx % map_width;
+        let y = *idx / map_width;
+        let monster = spawn_named_entity(ecs, name, x as i32, y as i32);
+        if let (Some(monster), true) = (monster, *asleep) {
+            if let Some(ai) = ecs.write_storage::<Ai>().get_mut(monster) {
+                ai.state = AiState::Sleeping;
+            }
+        }
+    }
+    for (idx, name) in item_spawn_points.iter() {
+        let x = *idx % map_width;
+        let y = *idx / map_width;
+        spawn_named_entity(ecs, name, x as i32, y as i32);
     }
-    for idx in item_spawn_points.iter() {
-        let x = *idx % MAP_WIDTH;
-        let y = *idx / MAP_WIDTH;
-        random_item(ecs, x as i32, y as i32);
+    for (idx, name) in trap_spawn_points.iter() {
+        let x = *idx % map_width;
+        let y = *idx / map_width;
+        spawn_named_entity(ecs, name, x as i32, y as i32);
     }
 }
@@ -0,0 +1,136 @@
+use super::{
+    gamelog::GameLog, CombatStats, DamageType, Hit, MyTurn, SerializeMe, StatusEffect, StatusKind,
+    SufferDamage, Viewshed,
+};
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+
+/// Hit points lost to poison, or regained by regeneration, on each of the target's turns.
+const POISON_DAMAGE: i32 = 1;
+const REGENERATION_HEAL: i32 = 1;
+
+/// Whether `target` is currently under `kind`.
+pub fn has_status(statuses: &ReadStorage<StatusEffect>, target: Entity, kind: StatusKind) -> bool {
+    statuses
+        .join()
+        .any(|status| status.target == target && status.kind == kind)
+}
+
+/// Starts `kind` on `target` for `turns` turns. A target that already has it keeps whichever
+/// duration is longer rather than stacking a second copy, and the player keeps the credit for it
+/// once they have inflicted it.
+pub fn apply_status(
+    entities: &Entities,
+    lazy: &LazyUpdate,
+    statuses: &mut WriteStorage<StatusEffect>,
+    target: Entity,
+    kind: StatusKind,
+    turns: i32,
+    from_player: bool,
+) {
+    for status in (statuses).join() {
+        if status.target == target && status.kind == kind {
+            status.turns = i32::max(status.turns, turns);
+            status.from_player |= from_player;
+            return;
+        }
+    }
+
+    lazy.create_entity(entities)
+        .with(StatusEffect {
+            target,
+            kind,
+            turns,
+            from_player,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+/// Counts down every status effect on its target's turns (when it has `MyTurn`) and applies the
+/// ones that act each turn. Effects are entities of their own, so they end by deleting themselves.
+pub struct StatusEffectSystem {}
+
+impl<'a> System<'a> for StatusEffectSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, StatusEffect>,
+        ReadStorage<'a, MyTurn>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        WriteStorage<'a, Viewshed>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_entity,
+            mut log,
+            mut statuses,
+            turns,
+            mut combat_stats,
+            mut inflict_damage,
+            mut viewsheds,
+        ) = data;
+
+        for (effect_entity, status) in (&entities, &mut statuses).join() {
+            if !entities.is_alive(status.target) {
+                entities
+                    .delete(effect_entity)
+                    .expect("Unable to delete status");
+                continue;
+            }
+
+            if turns.get(status.target).is_none() {
+                continue;
+            }
+            let is_player = status.target == *player_entity;
+
+            match status.kind {
+                StatusKind::Poison => {
+                    SufferDamage::new_damage(
+                        &mut inflict_damage,
+                        status.target,
+                        Hit {
+                            amount: POISON_DAMAGE,
+                            damage_type: DamageType::Poison,
+                            from_player: status.from_player,
+                            source: "poison".to_string(),
+                        },
+                    );
+                    if is_player {
+                        log.entries
+                            .push(format!("The poison burns, for {} hp.", POISON_DAMAGE));
+                    }
+                }
+                StatusKind::Regeneration => {
+                    if let Some(stats) = combat_stats.get_mut(status.target) {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + REGENERATION_HEAL);
+                    }
+                }
+                _ => {}
+            }
+
+            status.turns -= 1;
+            if status.turns < 1 {
+                if status.kind == StatusKind::Blindness {
+                    if let Some(viewshed) = viewsheds.get_mut(status.target) {
+                        viewshed.dirty = true;
+                    }
+                }
+                if is_player {
+                    log.entries.push(format!(
+                        "You are no longer {}.",
+                        status.kind.name().to_lowercase()
+                    ));
+                }
+                entities
+                    .delete(effect_entity)
+                    .expect("Unable to delete status");
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use super::status_effect_system::apply_status;
+use super::{
+    gamelog::GameLog, EntityMoved, EntryTrigger, Hidden, Hit, InflictsDamage, InflictsStatus, Map,
+    Name, Position, SingleActivation, StatusEffect, SufferDamage,
+};
+use specs::prelude::*;
+
+/// Springs the traps under anything that moved this turn, whether it is the player or a monster.
+pub struct TriggerSystem {}
+
+impl<'a> System<'a> for TriggerSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, GameLog>,
+        Read<'a, LazyUpdate>,
+        WriteStorage<'a, EntityMoved>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, EntryTrigger>,
+        WriteStorage<'a, Hidden>,
+        ReadStorage<'a, SingleActivation>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, InflictsDamage>,
+        ReadStorage<'a, InflictsStatus>,
+        WriteStorage<'a, SufferDamage>,
+        WriteStorage<'a, StatusEffect>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_entity,
+            map,
+            mut log,
+            lazy,
+            mut entity_moved,
+            position,
+            entry_trigger,
+            mut hidden,
+            single_activation,
+            names,
+            inflicts_damage,
+            inflicts_status,
+            mut inflict_damage,
+            mut statuses,
+        ) = data;
+
+        let mut used_up: Vec<Entity> = Vec::new();
+        for (entity, _moved, pos) in (&entities, &entity_moved, &position).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+            for trap in map.tile_content[idx].iter() {
+                if entity == *trap || entry_trigger.get(*trap).is_none() {
+                    continue;
+                }
+
+                hidden.remove(*trap);
+                if entity == *player_entity || map.visible_tiles[idx] {
+                    if let (Some(victim), Some(trap_name)) = (names.get(entity), names.get(*trap)) {
+                        log.entries
+                            .push(format!("{} triggers the {}!", victim.name, trap_name.name));
+                    }
+                }
+
+                if let Some(damage) = inflicts_damage.get(*trap) {
+                    SufferDamage::new_damage(
+                        &mut inflict_damage,
+                        entity,
+                        Hit {
+                            amount: damage.damage,
+                            damage_type: damage.damage_type,
+                            from_player: false,
+                            source: names.get(*trap).map_or("trap", |n| &n.name).to_string(),
+                        },
+                    );
+                }
+                if let Some(status) = inflicts_status.get(*trap) {
+                    apply_status(
+                        &entities,
+                        &lazy,
+                        &mut statuses,
+                        entity,
+                        status.kind,
+                        status.turns,
+                        false,
+                    );
+                }
+
+                if single_activation.get(*trap).is_some() {
+                    used_up.push(*trap);
+                }
+            }
+        }
+
+        for trap in used_up.iter() {
+            entities.delete(*trap).expect("Unable to delete trap");
+        }
+
+        entity_moved.clear();
+    }
+}
@@ -0,0 +1,81 @@
+use super::run_stats::RunStats;
+use super::status_effect_system::has_status;
+use super::{Energy, Equipped, MyTurn, RunState, SpeedBonus, StatusEffect, StatusKind};
+use specs::prelude::*;
+
+/// Energy an actor needs before it may act.
+pub const TURN_THRESHOLD: i32 = 100;
+/// Speed of an ordinary creature; twice this acts twice as often.
+pub const NORMAL_SPEED: i32 = 10;
+
+/// What each kind of action costs in energy.
+pub const MOVE_COST: i32 = 100;
+pub const ATTACK_COST: i32 = 100;
+pub const USE_ITEM_COST: i32 = 100;
+pub const PICKUP_COST: i32 = 50;
+pub const DROP_COST: i32 = 50;
+pub const EQUIP_COST: i32 = 50;
+
+/// Advances time by one tick while `RunState::Ticking`: every actor gains energy equal to its
+/// speed, those with enough energy are given `MyTurn`, and the game waits for input once the
+/// player is one of them.
+pub struct TurnSystem {}
+
+impl<'a> System<'a> for TurnSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunState>,
+        WriteExpect<'a, RunStats>,
+        WriteStorage<'a, Energy>,
+        WriteStorage<'a, MyTurn>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, SpeedBonus>,
+        ReadStorage<'a, StatusEffect>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_entity,
+            mut runstate,
+            mut run_stats,
+            mut energy,
+            mut turns,
+            equipped,
+            speed_bonuses,
+            statuses,
+        ) = data;
+
+        if *runstate != RunState::Ticking {
+            return;
+        }
+
+        for (entity, energy) in (&entities, &mut energy).join() {
+            let gear_speed: i32 = (&speed_bonuses, &equipped)
+                .join()
+                .filter(|(_, equipped_by)| equipped_by.owner == entity)
+                .map(|(bonus, _)| bonus.speed)
+                .sum();
+            let mut speed = energy.speed + gear_speed;
+            if has_status(&statuses, entity, StatusKind::Haste) {
+                speed *= 2;
+            }
+            if has_status(&statuses, entity, StatusKind::Slow) {
+                speed /= 2;
+            }
+            energy.current += i32::max(1, speed);
+
+            if energy.current >= TURN_THRESHOLD {
+                turns
+                    .insert(entity, MyTurn {})
+                    .expect("Unable to insert turn");
+                if entity == *player_entity {
+                    run_stats.turns += 1;
+                    *runstate = RunState::AwaitingInput;
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,11 @@
-use super::{Map, Player, Position, Viewshed};
+use super::status_effect_system::has_status;
+use super::{Map, Player, Position, StatusEffect, StatusKind, Viewshed};
 use rltk::{field_of_view, Point};
 use specs::prelude::*;
 
+/// How far a blinded creature can still make out its surroundings.
+const BLIND_RANGE: i32 = 1;
+
 pub struct VisibilitySystem;
 
 impl<'a> System<'a> for VisibilitySystem {
@@ -11,16 +15,22 @@ impl<'a> System<'a> for VisibilitySystem {
         WriteStorage<'a, Viewshed>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, Player>,
+        ReadStorage<'a, StatusEffect>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, entities, mut viewshed, pos, player) = data;
+        let (mut map, entities, mut viewshed, pos, player, statuses) = data;
+        // A door opening or closing changes what everyone can see, not just whoever moved it
+        let view_blocked_changed = map.view_blocked_changed;
+        map.view_blocked_changed = false;
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
-            if viewshed.dirty {
+            // Blindness is re-checked every turn, since it can start without the viewer moving
+            let blind = has_status(&statuses, ent, StatusKind::Blindness);
+            if viewshed.dirty || blind || view_blocked_changed {
                 viewshed.dirty = false;
                 viewshed.visible_tiles.clear();
-                viewshed.visible_tiles =
-                    field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map);
+                let range = if blind { BLIND_RANGE } else { viewshed.range };
+                viewshed.visible_tiles = field_of_view(Point::new(pos.x, pos.y), range, &*map);
                 viewshed
                     .visible_tiles
                     .retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height);
@@ -0,0 +1,1572 @@
+use rltk::{BaseMap, Point, RandomNumberGenerator, VirtualKeyCode};
+use rogue_rs::camera::{camera_origin, screen_to_world, world_to_screen, VIEW_HEIGHT, VIEW_WIDTH};
+use rogue_rs::components::{
+    Ai, AiState, CombatStats, DamageType, Door, Energy, EquipmentSlot, Equipped, Experience, Hit,
+    HungerClock, HungerState, InBackpack, Item, Monster, Name, Position, RunState, StatusEffect,
+    StatusKind, SufferDamage, Viewshed,
+};
+use rogue_rs::gui::LevelUpStat;
+use rogue_rs::headless::{Command, Headless};
+use rogue_rs::high_scores::{insert_ranked, load_high_scores, score, HighScore, MAX_HIGH_SCORES};
+use rogue_rs::map::{Map, TileType};
+use rogue_rs::map_builders::prefabs::{template_rows, SECTIONS, VAULTS};
+use rogue_rs::map_builders::{
+    builder_by_name, MapBuilder, PrefabBuilder, BUILDER_NAMES, LEVEL_HEIGHT, LEVEL_WIDTH,
+};
+use rogue_rs::melee_combat_system::{attack_roll, AttackRoll};
+use rogue_rs::monster_ai_system::SEARCH_TURNS;
+use rogue_rs::raws::{spawn_named_entity, RawMaster};
+use rogue_rs::record_paths::RecordPaths;
+use rogue_rs::replay::{Replay, ReplayMode};
+use rogue_rs::run_stats::RunStats;
+use rogue_rs::spawner;
+use rogue_rs::turn_system::{MOVE_COST, TURN_THRESHOLD};
+use rogue_rs::State;
+use specs::prelude::*;
+use std::path::Path;
+
+const SEED: u64 = 1234;
+
+/// Starts a game on the plain rooms-and-corridors layout, so scenarios have open floor to work with.
+fn new_game() -> Headless {
+    Headless::with_builder(SEED, "rooms")
+}
+
+/// Spawns `name` under the player, picks it up and equips it.
+fn pick_up_and_equip(harness: &mut Headless, name: &str) -> Entity {
+    let pos = harness.player_position();
+    let item = spawn_named_entity(harness.ecs_mut(), name, pos.x, pos.y).unwrap();
+    harness.refresh();
+    harness.command(Command::PickUp);
+    harness.command(Command::UseItem { item, target: None });
+    item
+}
+
+/// Gives `entity` enough energy to act on the next tick, as if it had been waiting all along.
+fn ready_to_act(harness: &mut Headless, entity: Entity) {
+    harness
+        .ecs_mut()
+        .write_storage::<Energy>()
+        .get_mut(entity)
+        .unwrap()
+        .current = TURN_THRESHOLD;
+}
+
+/// Lets the player sit out a turn, as if they had spent it on a move.
+fn wait_a_turn(harness: &mut Headless) {
+    let player = harness.player();
+    harness
+        .ecs_mut()
+        .write_storage::<Energy>()
+        .get_mut(player)
+        .unwrap()
+        .current -= MOVE_COST;
+    *harness.ecs_mut().write_resource::<RunState>() = RunState::PlayerTurn;
+    harness.run_until_input();
+}
+
+/// Removes every monster spawned with the level so a test only deals with its own.
+fn clear_monsters(harness: &mut Headless) {
+    let monsters: Vec<Entity> = {
+        let entities = harness.ecs().entities();
+        let monsters = harness.ecs().read_storage::<Monster>();
+        (&entities, &monsters).join().map(|(e, _)| e).collect()
+    };
+    for monster in monsters {
+        harness.ecs_mut().delete_entity(monster).unwrap();
+    }
+    harness.refresh();
+}
+
+/// The AI state of `monster`.
+fn ai_state(harness: &Headless, monster: Entity) -> AiState {
+    harness
+        .ecs()
+        .read_storage::<Ai>()
+        .get(monster)
+        .unwrap()
+        .state
+}
+
+#[test]
+fn same_seed_builds_the_same_dungeon() {
+    let a = Headless::new(SEED);
+    let b = Headless::new(SEED);
+
+    assert!(a.map().tiles == b.map().tiles);
+    assert_eq!(a.player_position().x, b.player_position().x);
+    assert_eq!(a.player_position().y, b.player_position().y);
+    assert_eq!(a.map().depth, 1);
+}
+
+#[test]
+fn attacking_a_monster_damages_it_and_it_strikes_back() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    let orc = spawn_named_entity(harness.ecs_mut(), "Orc", pos.x + 1, pos.y).unwrap();
+    ready_to_act(&mut harness, orc);
+    harness.refresh();
+
+    // Attacks can miss, so keep swinging until one lands
+    let orc_hp = |harness: &Headless| {
+        harness
+            .ecs()
+            .read_storage::<CombatStats>()
+            .get(orc)
+            .unwrap()
+            .hp
+    };
+    for _ in 0..10 {
+        if orc_hp(&harness) < 16 {
+            break;
+        }
+        harness.command(Command::Move {
+            delta_x: 1,
+            delta_y: 0,
+        });
+    }
+
+    let damage = 16 - orc_hp(&harness);
+    assert!(damage > 0);
+    let log = harness.log();
+    assert!(
+        log.contains(&format!("Player hits Orc, for {} hp.", damage))
+            || log.contains(&format!("Player critically hits Orc, for {} hp!", damage))
+    );
+    assert!(log
+        .iter()
+        .any(|entry| entry.starts_with("Orc ") && entry.contains("Player")));
+}
+
+#[test]
+fn monsters_chase_a_visible_player() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    let orc = spawn_named_entity(harness.ecs_mut(), "Orc", pos.x + 2, pos.y).unwrap();
+    ready_to_act(&mut harness, orc);
+    harness.refresh();
+
+    harness.command(Command::Move {
+        delta_x: -1,
+        delta_y: 0,
+    });
+
+    let orc_pos = harness
+        .ecs()
+        .read_storage::<Position>()
+        .get(orc)
+        .unwrap()
+        .clone();
+    assert_eq!(orc_pos.x, pos.x + 1);
+}
+
+#[test]
+fn fast_monsters_act_more_often() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let player = harness.player();
+    harness
+        .ecs_mut()
+        .write_storage::<Energy>()
+        .get_mut(player)
+        .unwrap()
+        .current = TURN_THRESHOLD;
+    let pos = harness.player_position();
+    spawn_named_entity(harness.ecs_mut(), "Orc", pos.x - 1, pos.y);
+    spawn_named_entity(harness.ecs_mut(), "Goblin", pos.x + 1, pos.y);
+    let potion = spawn_named_entity(harness.ecs_mut(), "Health Potion", pos.x, pos.y).unwrap();
+    harness.refresh();
+
+    // Using an item costs a full turn, in which a goblin at twice the speed acts twice
+    harness.command(Command::UseItem {
+        item: potion,
+        target: None,
+    });
+
+    // Hit or miss, every attack leaves one line in the log
+    let log = harness.log();
+    let attacks = |attacker: &str| {
+        log.iter()
+            .filter(|entry| entry.starts_with(attacker) && entry.contains("Player"))
+            .count()
+    };
+    assert_eq!(attacks("Orc "), 1);
+    assert_eq!(attacks("Goblin "), 2);
+}
+
+#[test]
+fn confused_monsters_lose_their_turns() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    let orc = spawn_named_entity(harness.ecs_mut(), "Orc", pos.x + 1, pos.y).unwrap();
+    ready_to_act(&mut harness, orc);
+    let scroll = spawn_named_entity(harness.ecs_mut(), "Confusion Scroll", pos.x, pos.y).unwrap();
+    harness.refresh();
+
+    harness.command(Command::UseItem {
+        item: scroll,
+        target: Some(Point::new(pos.x + 1, pos.y)),
+    });
+
+    assert_eq!(harness.player_stats().hp, 30);
+    assert!(!harness.ecs().is_alive(scroll));
+    assert!(harness
+        .log()
+        .contains(&"You use Confusion Scroll on Orc, leaving them confused.".to_string()));
+}
+
+#[test]
+fn sleeping_monsters_wake_when_the_player_comes_close() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    let orc = spawn_named_entity(harness.ecs_mut(), "Orc", pos.x + 3, pos.y).unwrap();
+    harness
+        .ecs_mut()
+        .write_storage::<Ai>()
+        .get_mut(orc)
+        .unwrap()
+        .state = AiState::Sleeping;
+    ready_to_act(&mut harness, orc);
+    harness.refresh();
+
+    harness.command(Command::Move {
+        delta_x: 1,
+        delta_y: 0,
+    });
+
+    assert!(harness.log().contains(&"Orc wakes up.".to_string()));
+    assert!(matches!(ai_state(&harness, orc), AiState::Chasing { .. }));
+}
+
+#[test]
+fn badly_hurt_monsters_flee() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    let orc = spawn_named_entity(harness.ecs_mut(), "Orc", pos.x + 1, pos.y).unwrap();
+    harness
+        .ecs_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(orc)
+        .unwrap()
+        .hp = 3;
+    ready_to_act(&mut harness, orc);
+    harness.refresh();
+
+    harness.command(Command::Move {
+        delta_x: -1,
+        delta_y: 0,
+    });
+
+    assert!(ai_state(&harness, orc) == AiState::Fleeing);
+    let orc_pos = harness
+        .ecs()
+        .read_storage::<Position>()
+        .get(orc)
+        .unwrap()
+        .clone();
+    let player_pos = harness.player_position();
+    let distance = rltk::DistanceAlg::Pythagoras.distance2d(
+        Point::new(orc_pos.x, orc_pos.y),
+        Point::new(player_pos.x, player_pos.y),
+    );
+    assert!(distance > 2.0);
+}
+
+#[test]
+fn status_scrolls_only_affect_creatures() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    let goblin = spawn_named_entity(harness.ecs_mut(), "Goblin", pos.x + 1, pos.y).unwrap();
+    spawn_named_entity(harness.ecs_mut(), "Health Potion", pos.x + 1, pos.y).unwrap();
+    let scroll = spawn_named_entity(harness.ecs_mut(), "Blindness Scroll", pos.x, pos.y).unwrap();
+    harness.refresh();
+    harness.command(Command::PickUp);
+
+    harness.command(Command::UseItem {
+        item: scroll,
+        target: Some(Point::new(pos.x + 1, pos.y)),
+    });
+
+    let statuses = harness.ecs().read_storage::<StatusEffect>();
+    let targets: Vec<Entity> = statuses.join().map(|status| status.target).collect();
+    assert_eq!(targets, vec![goblin]);
+}
+
+#[test]
+fn gas_traps_confuse_the_player() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    let trap =
+        spawn_named_entity(harness.ecs_mut(), "Confusion Gas Trap", pos.x + 1, pos.y).unwrap();
+    harness.refresh();
+
+    harness.command(Command::Move {
+        delta_x: 1,
+        delta_y: 0,
+    });
+
+    assert!(!harness.ecs().is_alive(trap));
+    assert!(harness
+        .log()
+        .contains(&"Player triggers the Confusion Gas Trap!".to_string()));
+    {
+        let statuses = harness.ecs().read_storage::<StatusEffect>();
+        let status = statuses.join().next().unwrap();
+        assert_eq!(status.target, harness.player());
+        assert!(status.kind == StatusKind::Confusion);
+    }
+
+    harness.command(Command::Move {
+        delta_x: 1,
+        delta_y: 0,
+    });
+    assert!(harness
+        .log()
+        .contains(&"You stumble around in confusion!".to_string()));
+}
+
+#[test]
+fn status_effects_tick_on_the_targets_turns_and_wear_off() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let player = harness.player();
+    harness
+        .ecs_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(player)
+        .unwrap()
+        .hp = 10;
+    let pos = harness.player_position();
+    let potion =
+        spawn_named_entity(harness.ecs_mut(), "Regeneration Potion", pos.x, pos.y).unwrap();
+    harness.refresh();
+
+    harness.command(Command::UseItem {
+        item: potion,
+        target: None,
+    });
+    assert_eq!(harness.player_stats().hp, 11);
+
+    let regeneration = {
+        let entities = harness.ecs().entities();
+        let statuses = harness.ecs().read_storage::<StatusEffect>();
+        let (entity, status) = (&entities, &statuses).join().next().unwrap();
+        assert_eq!(status.target, player);
+        assert!(status.kind == StatusKind::Regeneration);
+        entity
+    };
+    harness
+        .ecs_mut()
+        .write_storage::<StatusEffect>()
+        .get_mut(regeneration)
+        .unwrap()
+        .turns = 1;
+
+    harness.command(Command::Move {
+        delta_x: 1,
+        delta_y: 0,
+    });
+    assert_eq!(harness.player_stats().hp, 12);
+    assert!(!harness.ecs().is_alive(regeneration));
+    assert!(harness
+        .log()
+        .contains(&"You are no longer regenerating.".to_string()));
+}
+
+#[test]
+fn items_can_be_picked_up_and_used() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    spawn_named_entity(harness.ecs_mut(), "Health Potion", pos.x, pos.y);
+    harness.refresh();
+
+    harness.command(Command::PickUp);
+    let potion = {
+        let entities = harness.ecs().entities();
+        let backpack = harness.ecs().read_storage::<InBackpack>();
+        let (potion, pack) = (&entities, &backpack).join().next().unwrap();
+        assert_eq!(pack.owner, harness.player());
+        potion
+    };
+    assert!(harness
+        .log()
+        .contains(&"You picked up the Health Potion.".to_string()));
+
+    let player = harness.player();
+    harness
+        .ecs_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(player)
+        .unwrap()
+        .hp = 10;
+    harness.command(Command::UseItem {
+        item: potion,
+        target: None,
+    });
+
+    assert_eq!(harness.player_stats().hp, 18);
+    assert!(!harness.ecs().is_alive(potion));
+}
+
+#[test]
+fn attack_rolls_fumble_miss_hit_and_crit() {
+    assert_eq!(attack_roll(1, 100, 10), AttackRoll::Fumble);
+    assert_eq!(attack_roll(9, 5, 15), AttackRoll::Miss);
+    assert_eq!(attack_roll(10, 5, 15), AttackRoll::Hit);
+    assert_eq!(attack_roll(20, 0, 100), AttackRoll::Critical);
+}
+
+#[test]
+fn equipped_weapons_roll_their_own_damage_dice() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    pick_up_and_equip(&mut harness, "Longsword");
+
+    // A punching bag that never fights back
+    let pos = harness.player_position();
+    let orc = spawn_named_entity(harness.ecs_mut(), "Orc", pos.x + 1, pos.y).unwrap();
+    harness.ecs_mut().write_storage::<Energy>().remove(orc);
+    harness
+        .ecs_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(orc)
+        .unwrap()
+        .hp = 1000;
+    harness.refresh();
+    for _ in 0..30 {
+        harness.command(Command::Move {
+            delta_x: 1,
+            delta_y: 0,
+        });
+    }
+
+    // 1d8+2 on a hit, 2d8+2 on a critical
+    let damage_after = |entry: &str, prefix: &str| -> Option<i32> {
+        entry
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.split(' ').next())
+            .and_then(|n| n.parse().ok())
+    };
+    let log = harness.log();
+    let hits: Vec<i32> = log
+        .iter()
+        .filter_map(|entry| damage_after(entry, "Player hits Orc, for "))
+        .collect();
+    let crits: Vec<i32> = log
+        .iter()
+        .filter_map(|entry| damage_after(entry, "Player critically hits Orc, for "))
+        .collect();
+    assert!(!hits.is_empty());
+    assert!(hits.iter().all(|damage| (3..=10).contains(damage)));
+    assert!(crits.iter().all(|damage| (4..=18).contains(damage)));
+}
+
+#[test]
+fn damage_types_respect_resistances_and_immunities() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    pick_up_and_equip(&mut harness, "Dragonscale Mail");
+
+    let pos = harness.player_position();
+    let elemental =
+        spawn_named_entity(harness.ecs_mut(), "Fire Elemental", pos.x + 1, pos.y).unwrap();
+    harness
+        .ecs_mut()
+        .write_storage::<Energy>()
+        .remove(elemental);
+    let fireball = spawn_named_entity(harness.ecs_mut(), "Fireball Scroll", pos.x, pos.y).unwrap();
+    harness.refresh();
+    harness.command(Command::PickUp);
+    let frost = spawn_named_entity(harness.ecs_mut(), "Frost Scroll", pos.x, pos.y).unwrap();
+    harness.refresh();
+    harness.command(Command::PickUp);
+
+    // The blast catches the player too, but the mail halves it
+    let target = Some(Point::new(pos.x + 1, pos.y));
+    harness.command(Command::UseItem {
+        item: fireball,
+        target,
+    });
+    assert_eq!(
+        harness
+            .ecs()
+            .read_storage::<CombatStats>()
+            .get(elemental)
+            .unwrap()
+            .hp,
+        20
+    );
+    assert_eq!(harness.player_stats().hp, 15);
+    let log = harness.log();
+    assert!(log.contains(&"Fire Elemental is immune to fire damage.".to_string()));
+    assert!(log.contains(&"Player resists 15 fire damage.".to_string()));
+
+    harness.command(Command::UseItem {
+        item: frost,
+        target,
+    });
+    assert!(harness
+        .log()
+        .contains(&"Fire Elemental takes 10 extra cold damage.".to_string()));
+    assert!(!harness.ecs().is_alive(elemental));
+}
+
+#[test]
+fn damage_to_monsters_out_of_sight_is_not_described() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let map = harness.map();
+    let hidden_idx = (0..map.tiles.len())
+        .find(|idx| map.tiles[*idx] == TileType::Floor && !map.visible_tiles[*idx])
+        .unwrap();
+    let (x, y) = (hidden_idx as i32 % map.width, hidden_idx as i32 / map.width);
+    let elemental = spawn_named_entity(harness.ecs_mut(), "Fire Elemental", x, y).unwrap();
+    harness
+        .ecs_mut()
+        .write_storage::<SufferDamage>()
+        .insert(
+            elemental,
+            SufferDamage {
+                hits: vec![Hit {
+                    amount: 10,
+                    damage_type: DamageType::Fire,
+                    from_player: true,
+                    source: "Fireball Scroll".to_string(),
+                }],
+            },
+        )
+        .unwrap();
+    harness.refresh();
+
+    assert_eq!(
+        harness
+            .ecs()
+            .read_storage::<CombatStats>()
+            .get(elemental)
+            .unwrap()
+            .hp,
+        20
+    );
+    assert!(!harness
+        .log()
+        .contains(&"Fire Elemental is immune to fire damage.".to_string()));
+}
+
+#[test]
+fn equipping_swaps_items_in_the_same_slot() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let dagger = pick_up_and_equip(&mut harness, "Dagger");
+    let longsword = pick_up_and_equip(&mut harness, "Longsword");
+
+    let player = harness.player();
+    {
+        let equipped = harness.ecs().read_storage::<Equipped>();
+        let backpack = harness.ecs().read_storage::<InBackpack>();
+        assert!(equipped.get(dagger).is_none());
+        assert_eq!(backpack.get(dagger).unwrap().owner, player);
+        let worn = equipped.get(longsword).unwrap();
+        assert_eq!(worn.owner, player);
+        assert!(worn.slot == EquipmentSlot::Weapon);
+    }
+
+    harness.command(Command::RemoveItem { item: longsword });
+    let equipped = harness.ecs().read_storage::<Equipped>();
+    let backpack = harness.ecs().read_storage::<InBackpack>();
+    assert!(equipped.get(longsword).is_none());
+    assert_eq!(backpack.get(longsword).unwrap().owner, player);
+}
+
+#[test]
+fn killing_a_monster_awards_xp_and_levels_up() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let player = harness.player();
+    harness
+        .ecs_mut()
+        .write_storage::<Experience>()
+        .get_mut(player)
+        .unwrap()
+        .xp = 20;
+    let pos = harness.player_position();
+    let goblin = spawn_named_entity(harness.ecs_mut(), "Goblin", pos.x + 1, pos.y).unwrap();
+    harness
+        .ecs_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(goblin)
+        .unwrap()
+        .hp = 1;
+    harness.refresh();
+
+    let runstate = harness.command(Command::Move {
+        delta_x: 1,
+        delta_y: 0,
+    });
+
+    assert!(runstate == RunState::LevelUp);
+    {
+        let experience = harness.ecs().read_storage::<Experience>();
+        let xp = experience.get(player).unwrap();
+        assert_eq!(xp.level, 2);
+        assert_eq!(xp.xp, 30);
+    }
+    assert_eq!(harness.player_stats().max_hp, 35);
+    assert_eq!(harness.player_stats().hp, 35);
+
+    let runstate = harness.command(Command::LevelUp {
+        stat: LevelUpStat::Power,
+    });
+    assert!(runstate == RunState::AwaitingInput);
+    assert_eq!(harness.player_stats().power, 6);
+}
+
+#[test]
+fn bumping_a_wall_or_finding_nothing_to_pick_up_takes_no_time() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    // An empty floor tile with a wall to its left
+    let map = harness.map();
+    let idx = (0..map.tiles.len())
+        .find(|idx| {
+            map.tiles[*idx] == TileType::Floor
+                && map.tiles[idx - 1] == TileType::Wall
+                && map.tile_content[*idx].is_empty()
+        })
+        .unwrap();
+    let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+    harness.teleport_player(x, y);
+    harness.refresh();
+
+    let player = harness.player();
+    let clock = |harness: &Headless| {
+        harness
+            .ecs()
+            .read_storage::<HungerClock>()
+            .get(player)
+            .unwrap()
+            .duration
+    };
+    let (turns, hunger) = (harness.ecs().fetch::<RunStats>().turns, clock(&harness));
+
+    let runstate = harness.command(Command::Move {
+        delta_x: -1,
+        delta_y: 0,
+    });
+    assert!(runstate == RunState::AwaitingInput);
+    assert!(harness.command(Command::PickUp) == RunState::AwaitingInput);
+    assert!(harness.press(VirtualKeyCode::H) == RunState::AwaitingInput);
+    assert!(harness.press(VirtualKeyCode::G) == RunState::AwaitingInput);
+
+    let pos = harness.player_position();
+    assert_eq!((pos.x, pos.y), (x, y));
+    assert_eq!(harness.ecs().fetch::<RunStats>().turns, turns);
+    assert_eq!(clock(&harness), hunger);
+}
+
+#[test]
+fn starving_hurts_until_the_player_eats() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let player = harness.player();
+    *harness
+        .ecs_mut()
+        .write_storage::<HungerClock>()
+        .get_mut(player)
+        .unwrap() = HungerClock {
+        state: HungerState::Hungry,
+        duration: 1,
+    };
+
+    wait_a_turn(&mut harness);
+    let state = harness
+        .ecs()
+        .read_storage::<HungerClock>()
+        .get(player)
+        .unwrap()
+        .state;
+    assert!(state == HungerState::Starving);
+    assert_eq!(harness.player_stats().hp, 30);
+
+    wait_a_turn(&mut harness);
+    assert_eq!(harness.player_stats().hp, 29);
+
+    let pos = harness.player_position();
+    let ration = spawn_named_entity(harness.ecs_mut(), "Ration", pos.x, pos.y).unwrap();
+    harness.refresh();
+    harness.command(Command::PickUp);
+    harness.command(Command::UseItem {
+        item: ration,
+        target: None,
+    });
+
+    let state = harness
+        .ecs()
+        .read_storage::<HungerClock>()
+        .get(player)
+        .unwrap()
+        .state;
+    assert!(state == HungerState::WellFed);
+    assert!(!harness.ecs().is_alive(ration));
+    assert!(harness.log().contains(&"You eat the Ration.".to_string()));
+}
+
+/// The names of the items the player carries and wears, and the status effects on them.
+fn belongings(harness: &Headless) -> (Vec<String>, Vec<String>, Vec<(StatusKind, i32)>) {
+    let ecs = harness.ecs();
+    let player = harness.player();
+    let names = ecs.read_storage::<Name>();
+    let mut backpack: Vec<String> = (&ecs.read_storage::<InBackpack>(), &names)
+        .join()
+        .filter(|(pack, _)| pack.owner == player)
+        .map(|(_, name)| name.name.clone())
+        .collect();
+    backpack.sort();
+    let equipped = (&ecs.read_storage::<Equipped>(), &names)
+        .join()
+        .filter(|(equipped, _)| equipped.owner == player)
+        .map(|(_, name)| name.name.clone())
+        .collect();
+    let statuses = ecs
+        .read_storage::<StatusEffect>()
+        .join()
+        .filter(|status| status.target == player)
+        .map(|status| (status.kind, status.turns))
+        .collect();
+    (backpack, equipped, statuses)
+}
+
+#[test]
+fn a_saved_game_continues_with_the_players_belongings() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    pick_up_and_equip(&mut harness, "Dagger");
+    let pos = harness.player_position();
+    for name in ["Health Potion", "Magic Missile Scroll"] {
+        spawn_named_entity(harness.ecs_mut(), name, pos.x, pos.y).unwrap();
+    }
+    harness.refresh();
+    harness.command(Command::PickUp);
+    harness.command(Command::PickUp);
+    pick_up_and_equip(&mut harness, "Regeneration Potion");
+
+    let before = belongings(&harness);
+    assert_eq!(
+        before.0,
+        vec![
+            "Health Potion".to_string(),
+            "Magic Missile Scroll".to_string()
+        ]
+    );
+    assert_eq!(before.1, vec!["Dagger".to_string()]);
+    assert!(before
+        .2
+        .iter()
+        .any(|(kind, _)| *kind == StatusKind::Regeneration));
+    let position = (pos.x, pos.y);
+
+    harness.state.save_game();
+    let save_file = harness.ecs().fetch::<RecordPaths>().save_file.clone();
+    assert!(save_file.exists());
+    let runstate = harness.state.continue_game();
+
+    assert!(runstate == RunState::AwaitingInput);
+    assert!(!save_file.exists());
+    assert!(belongings(&harness) == before);
+    let pos = harness.player_position();
+    assert_eq!((pos.x, pos.y), position);
+}
+
+#[test]
+fn dying_ends_the_run_and_writes_a_morgue_file() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    pick_up_and_equip(&mut harness, "Dagger");
+
+    let pos = harness.player_position();
+    spawn_named_entity(harness.ecs_mut(), "Goblin", pos.x + 1, pos.y);
+    let missile =
+        spawn_named_entity(harness.ecs_mut(), "Magic Missile Scroll", pos.x, pos.y).unwrap();
+    harness.refresh();
+    harness.command(Command::PickUp);
+    harness.command(Command::UseItem {
+        item: missile,
+        target: Some(Point::new(pos.x + 1, pos.y)),
+    });
+
+    // A poison dart thrown at their own feet finishes off a player on their last hit point
+    let player = harness.player();
+    harness
+        .ecs_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(player)
+        .unwrap()
+        .hp = 1;
+    let dart = spawn_named_entity(harness.ecs_mut(), "Poison Dart", pos.x, pos.y).unwrap();
+    harness.refresh();
+    harness.command(Command::PickUp);
+    let runstate = harness.command(Command::UseItem {
+        item: dart,
+        target: None,
+    });
+
+    assert!(runstate == RunState::GameOver);
+    let log = harness.log();
+    assert!(log.contains(&"You are dead.".to_string()));
+    let path = log
+        .iter()
+        .find_map(|entry| entry.strip_prefix("A record of your run was written to "))
+        .and_then(|rest| rest.strip_suffix('.'))
+        .expect("No morgue file was written")
+        .to_string();
+
+    let paths = harness.ecs().fetch::<RecordPaths>().clone();
+    assert!(Path::new(&path).starts_with(&paths.morgue_dir));
+    let morgue = std::fs::read_to_string(&path).unwrap();
+    assert!(morgue.contains("Seed: 1234"));
+    assert!(morgue.contains("Depth reached: 1"));
+    assert!(morgue.contains("Kills (1):\n  1 Goblin"));
+    assert!(morgue.contains("Dagger (Weapon)"));
+    assert!(morgue.contains("Turns survived: "));
+    assert!(morgue.contains("Killed by: Poison Dart"));
+    assert!(morgue.contains("You use Poison Dart on Player"));
+
+    let scores = load_high_scores(&paths);
+    assert_eq!(scores.len(), 1);
+    assert_eq!(scores[0].seed, SEED);
+    assert_eq!(scores[0].cause_of_death, "Poison Dart");
+}
+
+#[test]
+fn a_monster_killed_by_the_players_poison_counts_as_their_kill() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    let goblin = spawn_named_entity(harness.ecs_mut(), "Goblin", pos.x + 1, pos.y).unwrap();
+    // The dart itself leaves the goblin a couple of hit points, for the poison to finish on later
+    // turns
+    harness
+        .ecs_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(goblin)
+        .unwrap()
+        .hp = 5;
+    let dart = spawn_named_entity(harness.ecs_mut(), "Poison Dart", pos.x, pos.y).unwrap();
+    harness.refresh();
+    harness.command(Command::PickUp);
+    harness.command(Command::UseItem {
+        item: dart,
+        target: Some(Point::new(pos.x + 1, pos.y)),
+    });
+
+    for _ in 0..50 {
+        if !harness.ecs().is_alive(goblin) {
+            break;
+        }
+        ready_to_act(&mut harness, goblin);
+        wait_a_turn(&mut harness);
+    }
+
+    assert!(!harness.ecs().is_alive(goblin));
+    assert_eq!(harness.ecs().fetch::<RunStats>().kills, vec!["Goblin"]);
+    let player = harness.player();
+    assert_eq!(
+        harness
+            .ecs()
+            .read_storage::<Experience>()
+            .get(player)
+            .unwrap()
+            .xp,
+        10
+    );
+}
+
+#[test]
+fn high_scores_are_ranked_and_capped() {
+    let entry = |score: i32, seed: u64| HighScore {
+        score,
+        depth: 1,
+        kills: 0,
+        turns: 0,
+        seed,
+        cause_of_death: "Orc".to_string(),
+        date: "2024-01-01".to_string(),
+    };
+    assert_eq!(score(3, 5, 250), 375);
+
+    let mut scores = Vec::new();
+    for i in 0..MAX_HIGH_SCORES as i32 {
+        assert_eq!(
+            insert_ranked(&mut scores, entry(100 * i, i as u64)),
+            Some(1)
+        );
+    }
+
+    // Ties rank below the runs already on the table, and the lowest score drops off
+    assert_eq!(insert_ranked(&mut scores, entry(500, 99)), Some(6));
+    assert_eq!(scores[4].seed, 5);
+    assert_eq!(scores.len(), MAX_HIGH_SCORES);
+    assert_eq!(scores.last().unwrap().score, 100);
+    assert_eq!(insert_ranked(&mut scores, entry(50, 100)), None);
+}
+
+#[test]
+fn descending_keeps_the_backpack() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    spawn_named_entity(harness.ecs_mut(), "Health Potion", pos.x, pos.y);
+    harness.refresh();
+    harness.command(Command::PickUp);
+
+    let map = harness.map();
+    let stairs = map
+        .tiles
+        .iter()
+        .position(|t| *t == TileType::DownStairs)
+        .unwrap() as i32;
+    harness.teleport_player(stairs % map.width, stairs / map.width);
+    harness.command(Command::Descend);
+
+    assert_eq!(harness.map().depth, 2);
+    let items = harness.ecs().read_storage::<Item>();
+    let backpack = harness.ecs().read_storage::<InBackpack>();
+    assert_eq!((&items, &backpack).join().count(), 1);
+}
+
+#[test]
+fn spawn_tables_respect_depth() {
+    let harness = new_game();
+    let raws = harness.ecs().fetch::<RawMaster>();
+    let mut rng = RandomNumberGenerator::seeded(SEED);
+
+    for _ in 0..100 {
+        assert_eq!(
+            raws.monster_table(1).roll(&mut rng),
+            Some("Goblin".to_string())
+        );
+    }
+    let deeper: Vec<Option<String>> = (0..100)
+        .map(|_| raws.monster_table(5).roll(&mut rng))
+        .collect();
+    assert!(deeper.contains(&Some("Orc".to_string())));
+    assert!(raws.item_table(1).roll(&mut rng).is_some());
+}
+
+#[test]
+fn monsters_search_where_they_last_saw_the_player() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let pos = harness.player_position();
+    let orc = spawn_named_entity(harness.ecs_mut(), "Orc", pos.x + 3, pos.y).unwrap();
+    // A blinded orc can't see the player from where it stands, so it only has its memory to go on
+    harness
+        .ecs_mut()
+        .write_storage::<Ai>()
+        .get_mut(orc)
+        .unwrap()
+        .state = AiState::Chasing {
+        x: pos.x + 1,
+        y: pos.y,
+    };
+    harness
+        .ecs_mut()
+        .create_entity()
+        .with(StatusEffect {
+            target: orc,
+            kind: StatusKind::Blindness,
+            turns: 10,
+            from_player: false,
+        })
+        .build();
+    ready_to_act(&mut harness, orc);
+    harness.refresh();
+
+    harness.command(Command::Move {
+        delta_x: -1,
+        delta_y: 0,
+    });
+    harness.command(Command::Move {
+        delta_x: 1,
+        delta_y: 0,
+    });
+
+    let orc_pos = harness
+        .ecs()
+        .read_storage::<Position>()
+        .get(orc)
+        .unwrap()
+        .clone();
+    assert_eq!((orc_pos.x, orc_pos.y), (pos.x + 1, pos.y));
+    assert_eq!(
+        ai_state(&harness, orc),
+        AiState::Searching {
+            turns: SEARCH_TURNS
+        }
+    );
+}
+
+#[test]
+fn every_map_builder_makes_a_level_the_player_can_cross() {
+    for name in BUILDER_NAMES {
+        for seed in [SEED, 99, 2024] {
+            let harness = Headless::with_builder(seed, name);
+            let pos = harness.player_position();
+            let mut map = harness.map();
+            map.populate_blocked();
+
+            let start_idx = map.xy_idx(pos.x, pos.y);
+            assert!(
+                map.tiles[start_idx] == TileType::Floor,
+                "{} starts in a wall",
+                name
+            );
+            let stairs_idx = map
+                .tiles
+                .iter()
+                .position(|tile| *tile == TileType::DownStairs)
+                .unwrap_or_else(|| panic!("{} has no stairs down", name));
+            assert!(
+                rltk::a_star_search(start_idx, stairs_idx, &map).success,
+                "{} has no way to the stairs",
+                name
+            );
+        }
+    }
+}
+
+#[test]
+fn a_requested_builder_is_used_on_every_level() {
+    let mut harness = Headless::with_builder(SEED, "maze");
+    let stairs_idx = harness
+        .map()
+        .tiles
+        .iter()
+        .position(|tile| *tile == TileType::DownStairs)
+        .unwrap();
+    let width = harness.map().width;
+    harness.teleport_player(stairs_idx as i32 % width, stairs_idx as i32 / width);
+    harness.command(Command::Descend);
+
+    assert_eq!(harness.map().depth, 2);
+    // Mazes start in the top left cell
+    let pos = harness.player_position();
+    assert_eq!((pos.x, pos.y), (1, 1));
+}
+
+#[test]
+fn map_builders_record_how_the_level_was_built() {
+    for name in BUILDER_NAMES {
+        let mut builder = builder_by_name(name, 1, LEVEL_WIDTH, LEVEL_HEIGHT).unwrap();
+        builder.build_map(&mut RandomNumberGenerator::seeded(SEED));
+
+        let history = builder.get_snapshot_history();
+        assert!(!history.is_empty(), "{} took no snapshots", name);
+        let last = history.last().unwrap();
+        assert!(
+            last.tiles == builder.get_map().tiles,
+            "{} ends on a stale snapshot",
+            name
+        );
+        assert!(last.revealed_tiles.iter().all(|revealed| *revealed));
+    }
+}
+
+#[test]
+fn map_builders_work_at_any_size() {
+    // The premade crypt is always the size it was drawn at
+    for name in BUILDER_NAMES.iter().filter(|name| **name != "crypt") {
+        for (width, height) in [(40, 30), (120, 90)] {
+            let mut builder = builder_by_name(name, 1, width, height).unwrap();
+            builder.build_map(&mut RandomNumberGenerator::seeded(SEED));
+            let map = builder.get_map();
+            assert_eq!((map.width, map.height), (width, height));
+            assert_eq!(map.tiles.len(), (width * height) as usize);
+            assert!(
+                map.tiles.contains(&TileType::DownStairs),
+                "{} has no stairs",
+                name
+            );
+        }
+    }
+}
+
+#[test]
+fn the_camera_follows_the_player_but_stops_at_the_map_edges() {
+    let big = Map::new(1, 200, 100);
+    // Centred on the player in the middle of the map
+    let origin = camera_origin(&big, Point::new(100, 50));
+    assert_eq!(
+        (origin.x, origin.y),
+        (100 - VIEW_WIDTH / 2, 50 - VIEW_HEIGHT / 2)
+    );
+    let screen = world_to_screen(origin, Point::new(100, 50)).unwrap();
+    assert_eq!((screen.x, screen.y), (VIEW_WIDTH / 2, VIEW_HEIGHT / 2));
+    let world = screen_to_world(origin, screen);
+    assert_eq!((world.x, world.y), (100, 50));
+    assert!(world_to_screen(origin, Point::new(0, 0)).is_none());
+
+    // Pinned to the corners near the edges
+    let origin = camera_origin(&big, Point::new(1, 1));
+    assert_eq!((origin.x, origin.y), (0, 0));
+    let origin = camera_origin(&big, Point::new(199, 99));
+    assert_eq!((origin.x, origin.y), (200 - VIEW_WIDTH, 100 - VIEW_HEIGHT));
+
+    // Small maps sit in the middle of the screen
+    let small = Map::new(1, 40, 21);
+    let origin = camera_origin(&small, Point::new(0, 0));
+    assert_eq!((origin.x, origin.y), (-20, -11));
+}
+
+#[test]
+fn levels_are_generated_at_level_size() {
+    let harness = new_game();
+    let map = harness.map();
+    assert_eq!((map.width, map.height), (LEVEL_WIDTH, LEVEL_HEIGHT));
+}
+
+/// The name of whatever stands at `(x, y)`, if anything.
+fn names_at(harness: &Headless, x: i32, y: i32) -> Vec<String> {
+    let names = harness.ecs().read_storage::<Name>();
+    let positions = harness.ecs().read_storage::<Position>();
+    (&names, &positions)
+        .join()
+        .filter(|(_, pos)| pos.x == x && pos.y == y)
+        .map(|(name, _)| name.name.clone())
+        .collect()
+}
+
+#[test]
+fn the_premade_crypt_is_loaded_from_its_rex_paint_file() {
+    let harness = Headless::with_builder(SEED, "crypt");
+    let map = harness.map();
+    assert_eq!((map.width, map.height), (50, 20));
+
+    let pos = harness.player_position();
+    assert_eq!((pos.x, pos.y), (1, 1));
+    assert!(map.tiles[map.xy_idx(0, 0)] == TileType::Wall);
+    assert!(map.tiles[map.xy_idx(46, 17)] == TileType::DownStairs);
+    assert_eq!(names_at(&harness, 12, 2), vec!["Goblin".to_string()]);
+    assert_eq!(names_at(&harness, 3, 9), vec!["Health Potion".to_string()]);
+}
+
+#[test]
+fn a_cave_level_starts_in_its_largest_cave() {
+    // This seed's most central floor tile is a sealed pocket of two tiles
+    let harness = Headless::with_builder(235, "cellular");
+    let map = harness.map();
+    let floor = map
+        .tiles
+        .iter()
+        .filter(|tile| **tile != TileType::Wall)
+        .count();
+    assert!(floor > 500, "only {} tiles can be reached", floor);
+}
+
+#[test]
+fn prefab_templates_are_rectangular_and_vaults_keep_their_edges_open() {
+    for template in SECTIONS
+        .iter()
+        .map(|s| s.template)
+        .chain(VAULTS.iter().map(|v| v.template))
+    {
+        let rows = template_rows(template);
+        assert!(rows.iter().all(|row| row.len() == rows[0].len()));
+    }
+    for vault in VAULTS.iter() {
+        let rows = template_rows(vault.template);
+        let (width, height) = (rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, ch) in row.iter().enumerate() {
+                if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                    assert_ne!(*ch, '#', "vault blocks the floor around it");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn sections_are_stamped_where_they_are_placed_and_joined_up() {
+    let cave = builder_by_name("drunkard", 2, LEVEL_WIDTH, LEVEL_HEIGHT).unwrap();
+    // The orc barracks sit against the right edge, halfway down
+    let mut builder = PrefabBuilder::sectional(2, &SECTIONS[0], cave);
+    builder.build_map(&mut RandomNumberGenerator::seeded(SEED));
+    let map = builder.get_map();
+
+    let rows = template_rows(SECTIONS[0].template);
+    let x0 = LEVEL_WIDTH - rows[0].len() as i32 - 1;
+    let y0 = (LEVEL_HEIGHT - rows.len() as i32) / 2;
+    assert!(map.tiles[map.xy_idx(x0, y0)] == TileType::Wall);
+    assert!(map.tiles[map.xy_idx(x0 + 2, y0 + 2)] == TileType::Floor);
+
+    let start = builder.get_starting_position();
+    let mut map = map;
+    map.populate_blocked();
+    let start_idx = map.xy_idx(start.x, start.y);
+    let barracks_idx = map.xy_idx(x0 + 2, y0 + 2);
+    assert!(rltk::a_star_search(start_idx, barracks_idx, &map).success);
+}
+
+#[test]
+fn random_levels_with_prefabs_can_still_be_crossed() {
+    for seed in 0..40 {
+        let harness = Headless::new(seed);
+        let pos = harness.player_position();
+        let mut map = harness.map();
+        map.populate_blocked();
+
+        let start_idx = map.xy_idx(pos.x, pos.y);
+        let stairs_idx = map
+            .tiles
+            .iter()
+            .position(|tile| *tile == TileType::DownStairs)
+            .unwrap();
+        assert!(
+            rltk::a_star_search(start_idx, stairs_idx, &map).success,
+            "seed {} has no way to the stairs",
+            seed
+        );
+    }
+}
+
+#[test]
+fn random_spawns_keep_off_tiles_a_template_already_filled() {
+    for seed in 0..20 {
+        // Straight after generation, before any monster has had a turn to wander onto an item
+        let mut state = State::new(Some(seed), None, ReplayMode::Off);
+        state.new_game();
+        let player = *state.ecs.fetch::<Entity>();
+        let entities = state.ecs.entities();
+        let positions = state.ecs.read_storage::<Position>();
+        let monsters = state.ecs.read_storage::<Monster>();
+        let items = state.ecs.read_storage::<Item>();
+        // Monsters, items and traps are each spread out separately, so one of each may share a
+        // tile, but never two of a kind
+        let mut filled: Vec<(bool, bool, i32, i32)> = Vec::new();
+        for (entity, pos) in (&entities, &positions).join() {
+            if entity == player {
+                continue;
+            }
+            let spot = (
+                monsters.contains(entity),
+                items.contains(entity),
+                pos.x,
+                pos.y,
+            );
+            assert!(
+                !filled.contains(&spot),
+                "seed {} stacked two spawns on ({}, {})",
+                seed,
+                pos.x,
+                pos.y
+            );
+            filled.push(spot);
+        }
+    }
+}
+
+/// A door on the level with open floor on both sides of it, and the direction through it.
+fn door_between_floors(harness: &Headless) -> (Entity, Position, (i32, i32)) {
+    let map = harness.map();
+    let is_floor = |x: i32, y: i32| map.tiles[map.xy_idx(x, y)] == TileType::Floor;
+    let entities = harness.ecs().entities();
+    let doors = harness.ecs().read_storage::<Door>();
+    let positions = harness.ecs().read_storage::<Position>();
+    (&entities, &doors, &positions)
+        .join()
+        .find_map(|(door, _, pos)| {
+            [(1, 0), (0, 1)]
+                .into_iter()
+                .find(|(dx, dy)| {
+                    is_floor(pos.x - dx, pos.y - dy) && is_floor(pos.x + dx, pos.y + dy)
+                })
+                .map(|through| (door, pos.clone(), through))
+        })
+        .expect("the level should have a doorway")
+}
+
+fn door_is_open(harness: &Headless, door: Entity) -> bool {
+    harness.ecs().read_storage::<Door>().get(door).unwrap().open
+}
+
+#[test]
+fn doors_block_the_way_until_the_player_opens_them() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let (door, pos, (dx, dy)) = door_between_floors(&harness);
+    let door_idx = harness.map().xy_idx(pos.x, pos.y);
+    assert!(!door_is_open(&harness, door));
+    assert!(harness.map().blocked[door_idx]);
+    assert!(harness.map().is_opaque(door_idx));
+
+    // Bumping the door opens it without stepping through
+    harness.teleport_player(pos.x - dx, pos.y - dy);
+    harness.refresh();
+    harness.command(Command::Move {
+        delta_x: dx,
+        delta_y: dy,
+    });
+    assert!(door_is_open(&harness, door));
+    assert!(!harness.map().is_opaque(door_idx));
+    let player = harness.player_position();
+    assert_eq!((player.x, player.y), (pos.x - dx, pos.y - dy));
+
+    harness.command(Command::Move {
+        delta_x: dx,
+        delta_y: dy,
+    });
+    let player = harness.player_position();
+    assert_eq!((player.x, player.y), (pos.x, pos.y));
+
+    // It can't be closed on the player, only once they have stepped out of it
+    harness.command(Command::CloseDoor);
+    assert!(door_is_open(&harness, door));
+    harness.command(Command::Move {
+        delta_x: dx,
+        delta_y: dy,
+    });
+    harness.command(Command::CloseDoor);
+    assert!(!door_is_open(&harness, door));
+    assert!(harness.map().blocked[door_idx]);
+    assert!(harness.map().is_opaque(door_idx));
+}
+
+#[test]
+fn closing_passes_over_a_blocked_door_to_close_another() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let (blocked, pos, (dx, dy)) = door_between_floors(&harness);
+    let (x, y) = (pos.x - dx, pos.y - dy);
+    harness.teleport_player(x, y);
+    let map = harness.map();
+    let (ox, oy) = [
+        (-1, -1),
+        (0, -1),
+        (1, -1),
+        (-1, 0),
+        (1, 0),
+        (-1, 1),
+        (0, 1),
+        (1, 1),
+    ]
+    .into_iter()
+    .map(|(ox, oy)| (x + ox, y + oy))
+    .find(|(ox, oy)| {
+        let idx = map.xy_idx(*ox, *oy);
+        (*ox, *oy) != (pos.x, pos.y)
+            && map.tiles[idx] == TileType::Floor
+            && map.tile_content[idx].is_empty()
+    })
+    .expect("the doorway should have floor beside it");
+    let other = spawner::door(harness.ecs_mut(), ox, oy);
+    spawn_named_entity(harness.ecs_mut(), "Health Potion", pos.x, pos.y).unwrap();
+    for door in [blocked, other] {
+        harness
+            .ecs_mut()
+            .write_storage::<Door>()
+            .get_mut(door)
+            .unwrap()
+            .open = true;
+    }
+    harness.refresh();
+
+    harness.command(Command::CloseDoor);
+    assert!(door_is_open(&harness, blocked));
+    assert!(!door_is_open(&harness, other));
+    assert!(harness.log().contains(&"You close the door.".to_string()));
+    assert!(!harness
+        .log()
+        .contains(&"Something is in the way of the door.".to_string()));
+
+    // With only the blocked door left open, that is what the player is told
+    harness.command(Command::CloseDoor);
+    assert!(door_is_open(&harness, blocked));
+    assert_eq!(
+        harness.log().last().unwrap(),
+        "Something is in the way of the door."
+    );
+}
+
+#[test]
+fn chasing_monsters_open_doors_in_their_way() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let (door, pos, (dx, dy)) = door_between_floors(&harness);
+    harness.teleport_player(pos.x - dx, pos.y - dy);
+    let orc = spawn_named_entity(harness.ecs_mut(), "Orc", pos.x + dx, pos.y + dy).unwrap();
+    harness
+        .ecs_mut()
+        .write_storage::<Ai>()
+        .get_mut(orc)
+        .unwrap()
+        .state = AiState::Chasing {
+        x: pos.x - dx,
+        y: pos.y - dy,
+    };
+    ready_to_act(&mut harness, orc);
+    harness.refresh();
+    // Let a tick pass without the player doing anything
+    *harness.ecs_mut().write_resource::<RunState>() = RunState::Ticking;
+    harness.run_until_input();
+
+    // Opening the door takes the orc's turn, so it is still on the far side
+    assert!(door_is_open(&harness, door));
+    let orc_pos = harness
+        .ecs()
+        .read_storage::<Position>()
+        .get(orc)
+        .unwrap()
+        .clone();
+    assert_eq!((orc_pos.x, orc_pos.y), (pos.x + dx, pos.y + dy));
+}
+
+/// Whether `viewer` can currently see the tile `(x, y)`.
+fn can_see(harness: &Headless, viewer: Entity, x: i32, y: i32) -> bool {
+    harness
+        .ecs()
+        .read_storage::<Viewshed>()
+        .get(viewer)
+        .unwrap()
+        .visible_tiles
+        .contains(&Point::new(x, y))
+}
+
+#[test]
+fn opening_and_closing_a_door_changes_what_everyone_can_see() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let (_, pos, (dx, dy)) = door_between_floors(&harness);
+    let (player_x, player_y) = (pos.x - dx, pos.y - dy);
+    harness.teleport_player(player_x, player_y);
+    // Without energy the orc never takes a turn, so only the door changes its view
+    let orc = spawn_named_entity(harness.ecs_mut(), "Orc", pos.x + dx, pos.y + dy).unwrap();
+    harness.ecs_mut().write_storage::<Energy>().remove(orc);
+    harness.refresh();
+    assert!(!can_see(&harness, orc, player_x, player_y));
+
+    harness.command(Command::Move {
+        delta_x: dx,
+        delta_y: dy,
+    });
+    assert!(can_see(&harness, orc, player_x, player_y));
+
+    harness.command(Command::CloseDoor);
+    assert!(!can_see(&harness, orc, player_x, player_y));
+    let orc_idx = harness.map().xy_idx(pos.x + dx, pos.y + dy);
+    assert!(!harness.map().visible_tiles[orc_idx]);
+}
+
+#[test]
+fn a_missing_or_garbled_replay_is_an_error() {
+    let harness = new_game();
+    let dir = harness.ecs().fetch::<RecordPaths>().morgue_dir.clone();
+    assert!(Replay::load(&dir.join("missing.jsonl")).is_err());
+
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("garbled.jsonl");
+    std::fs::write(&path, "{\"seed\": 1}\nnot an input\n").unwrap();
+    assert!(Replay::load(&path).is_err());
+}
+
+#[test]
+fn a_replayed_death_leaves_no_records_and_hands_back_control() {
+    let mut harness = new_game();
+    clear_monsters(&mut harness);
+    let replay = Replay {
+        seed: SEED,
+        builder: None,
+        inputs: Vec::new(),
+    };
+    harness.state.replay = ReplayMode::playing(replay, 1);
+
+    let player = harness.player();
+    harness
+        .ecs_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(player)
+        .unwrap()
+        .hp = 1;
+    let pos = harness.player_position();
+    let dart = spawn_named_entity(harness.ecs_mut(), "Poison Dart", pos.x, pos.y).unwrap();
+    harness.refresh();
+    harness.command(Command::PickUp);
+    let runstate = harness.command(Command::UseItem {
+        item: dart,
+        target: None,
+    });
+
+    assert!(runstate == RunState::GameOver);
+    assert!(harness.log().contains(&"You are dead.".to_string()));
+    assert!(!harness
+        .log()
+        .iter()
+        .any(|entry| entry.starts_with("A record of your run")));
+    let paths = RecordPaths::clone(&harness.ecs().fetch());
+    assert!(load_high_scores(&paths).is_empty());
+    assert!(!paths.morgue_dir.exists());
+
+    // The Game Over screen is the player's to dismiss
+    assert!(harness.state.replay.next_input(runstate).is_none());
+    assert!(!harness.state.replay.is_playing());
+}
+
+#[test]
+fn a_recorded_session_plays_back_to_the_same_state() {
+    let dir = std::env::temp_dir().join(format!("rogue-rs-replay-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("run.jsonl");
+
+    let mut recorded = new_game();
+    recorded.state.replay = ReplayMode::recording(path.display().to_string());
+    let file = recorded
+        .state
+        .replay
+        .start_recording(SEED, Some("rooms".to_string()));
+    assert_eq!(file, Some(path.clone()));
+    let keys = [
+        VirtualKeyCode::L,
+        VirtualKeyCode::L,
+        VirtualKeyCode::J,
+        VirtualKeyCode::G,
+        VirtualKeyCode::H,
+        VirtualKeyCode::K,
+        VirtualKeyCode::C,
+        VirtualKeyCode::K,
+        VirtualKeyCode::Period,
+    ];
+    for key in keys {
+        recorded.press(key);
+    }
+    recorded.state.replay.finish_recording();
+
+    let replay = Replay::load(&path).unwrap();
+    assert_eq!(replay.seed, SEED);
+    assert_eq!(replay.builder.as_deref(), Some("rooms"));
+    assert_eq!(replay.inputs.len(), keys.len());
+
+    let mut played = Headless::with_builder(replay.seed, replay.builder.as_deref().unwrap());
+    let mut playback = ReplayMode::playing(replay, 1);
+    while let Some(input) = playback.next_input(RunState::AwaitingInput) {
+        played.press(input.key.unwrap());
+    }
+    assert!(!playback.is_playing());
+
+    let (a, b) = (recorded.player_position(), played.player_position());
+    assert_eq!((a.x, a.y), (b.x, b.y));
+    assert_eq!(recorded.player_stats().hp, played.player_stats().hp);
+    assert_eq!(recorded.log(), played.log());
+    assert!(recorded.map().revealed_tiles == played.map().revealed_tiles);
+
+    // The next run of the session gets a file of its own
+    let second = recorded.state.replay.start_recording(SEED, None);
+    assert_eq!(second, Some(dir.join("run-2.jsonl")));
+    std::fs::remove_dir_all(&dir).unwrap();
+}